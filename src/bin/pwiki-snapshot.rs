@@ -0,0 +1,90 @@
+//! Writes a snapshot of the full substance catalog to disk.
+//!
+//! ```text
+//! pwiki-snapshot [--endpoint URL] [--page-size N] [--concurrency N] OUTPUT
+//! ```
+
+use std::process::ExitCode;
+
+use pwiki_api::client::DEFAULT_ENDPOINT;
+use pwiki_api::{CatalogOptions, PwikiClient, RetryPolicy, Snapshot};
+
+const USAGE: &str =
+    "usage: pwiki-snapshot [--endpoint URL] [--page-size N] [--concurrency N] OUTPUT";
+
+struct Args {
+    endpoint: String,
+    options: CatalogOptions,
+    output: String,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut endpoint = DEFAULT_ENDPOINT.to_string();
+    let mut options = CatalogOptions::default();
+    let mut output = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("{arg} needs a value"));
+
+        match arg.as_str() {
+            "--endpoint" => endpoint = value()?,
+            "--page-size" => {
+                options.page_size = value()?.parse().map_err(|e| format!("--page-size: {e}"))?
+            }
+            "--concurrency" => {
+                options.concurrency = value()?
+                    .parse()
+                    .map_err(|e| format!("--concurrency: {e}"))?
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if output.is_none() && !arg.starts_with('-') => output = Some(arg),
+            _ => return Err(format!("unexpected argument {arg:?}\n{USAGE}")),
+        }
+    }
+
+    Ok(Args {
+        endpoint,
+        options,
+        output: output.ok_or(USAGE)?,
+    })
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let result = async {
+        let client = PwikiClient::builder()
+            .endpoint(args.endpoint)
+            .retry(RetryPolicy::default())
+            .build()?;
+        let snapshot = Snapshot::fetch(&client, args.options).await?;
+        snapshot.save(&args.output)?;
+
+        Ok::<_, pwiki_api::PwikiError>(snapshot)
+    }
+    .await;
+
+    match result {
+        Ok(snapshot) => {
+            eprintln!(
+                "wrote {} substances from {} to {}",
+                snapshot.len(),
+                snapshot.metadata.endpoint,
+                args.output
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
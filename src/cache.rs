@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use graphql_client::QueryBody;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::write_atomically;
+
+/// Settings for the opt-in response cache, see
+/// [`PwikiClientBuilder::cache`](crate::client::PwikiClientBuilder::cache).
+///
+/// Responses are cached per query and variables, and shared between clones
+/// of a client.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheOptions {
+    /// How long a response is served without contacting the endpoint.
+    pub ttl: Duration,
+    /// How long after `ttl` a response is still served while it is refreshed
+    /// in the background. Zero disables revalidation.
+    pub stale_while_revalidate: Duration,
+    /// The oldest responses are evicted first once this is reached.
+    pub max_entries: usize,
+}
+
+impl Default for CacheOptions {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(5 * 60),
+            stale_while_revalidate: Duration::ZERO,
+            max_entries: 1000,
+        }
+    }
+}
+
+/// Successful response bodies, keyed by [`ResponseCache::key`].
+#[derive(Debug)]
+pub(crate) struct ResponseCache {
+    options: CacheOptions,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    cached: Cached,
+    /// Monotonic copy of `cached.fetched_at`, for expiry.
+    inserted_at: Instant,
+    refreshing: bool,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Cached {
+    pub body: Arc<[u8]>,
+    /// When the endpoint returned `body`.
+    pub fetched_at: DateTime<Utc>,
+}
+
+pub(crate) enum Lookup {
+    Fresh(Cached),
+    /// Past its ttl but within the revalidation window. `revalidate` is set
+    /// for the first caller to see the entry in this state, who is expected
+    /// to refresh it.
+    Stale {
+        cached: Cached,
+        revalidate: bool,
+    },
+    Miss,
+}
+
+impl ResponseCache {
+    pub fn new(options: CacheOptions) -> Self {
+        Self {
+            options,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn key<V: Serialize>(query: &QueryBody<V>) -> String {
+        let variables = serde_json::to_string(&query.variables).unwrap_or_default();
+        format!("{}:{variables}", query.operation_name)
+    }
+
+    fn entries(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        // entries are replaced whole, so a panic elsewhere cannot leave one
+        // half written
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_expired(&self, entry: &Entry) -> bool {
+        entry.inserted_at.elapsed() >= self.options.ttl + self.options.stale_while_revalidate
+    }
+
+    pub fn get(&self, key: &str) -> Lookup {
+        let mut entries = self.entries();
+        let Some(entry) = entries.get_mut(key) else {
+            return Lookup::Miss;
+        };
+
+        if entry.inserted_at.elapsed() < self.options.ttl {
+            Lookup::Fresh(entry.cached.clone())
+        } else if !self.is_expired(entry) {
+            let revalidate = !entry.refreshing;
+            entry.refreshing = true;
+
+            Lookup::Stale {
+                cached: entry.cached.clone(),
+                revalidate,
+            }
+        } else {
+            entries.remove(key);
+            Lookup::Miss
+        }
+    }
+
+    pub fn insert(&self, key: String, body: Arc<[u8]>, fetched_at: DateTime<Utc>) {
+        if self.options.max_entries == 0 {
+            return;
+        }
+
+        let mut entries = self.entries();
+        entries.retain(|_, i| !self.is_expired(i));
+
+        if !entries.contains_key(&key) && entries.len() >= self.options.max_entries {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, i)| i.inserted_at)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                cached: Cached { body, fetched_at },
+                inserted_at: Instant::now(),
+                refreshing: false,
+            },
+        );
+    }
+
+    /// Lets the next caller retry a background refresh that failed.
+    pub fn revalidation_failed(&self, key: &str) {
+        if let Some(entry) = self.entries().get_mut(key) {
+            entry.refreshing = false;
+        }
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.entries().remove(key);
+    }
+
+    pub fn clear(&self) {
+        self.entries().clear();
+    }
+}
+
+/// The last successful `SubstanceQuery` response per substance name, kept
+/// on disk to fall back on when the endpoint cannot be reached.
+#[derive(Debug)]
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry<T> {
+    #[serde(with = "crate::snapshot::rfc3339")]
+    fetched_at: DateTime<Utc>,
+    data: T,
+}
+
+impl DiskCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        // keep file names portable, escaping everything else as hex
+        let file_name = name.bytes().fold(String::from("substance-"), |mut acc, i| {
+            if i.is_ascii_alphanumeric() || i == b'-' {
+                acc.push(i as char);
+            } else {
+                acc.push_str(&format!("_{i:02x}"));
+            }
+            acc
+        });
+
+        self.dir.join(file_name + ".json")
+    }
+
+    pub fn load<T: DeserializeOwned>(&self, name: &str) -> Option<(DateTime<Utc>, T)> {
+        let json = std::fs::read(self.path(name)).ok()?;
+        let entry: DiskEntry<T> = serde_json::from_slice(&json).ok()?;
+
+        Some((entry.fetched_at, entry.data))
+    }
+
+    /// Replaces the entry for `name` atomically, also against concurrent
+    /// stores of the same name.
+    pub fn store<T: Serialize>(
+        &self,
+        name: &str,
+        fetched_at: DateTime<Utc>,
+        data: &T,
+    ) -> crate::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+
+        write_atomically(&self.path(name), |writer| {
+            Ok(serde_json::to_writer(
+                writer,
+                &DiskEntry { fetched_at, data },
+            )?)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_concurrent_disk_store() {
+        let dir = std::env::temp_dir().join(format!("pwiki-concurrent-{}", std::process::id()));
+        let cache = Arc::new(DiskCache::new(dir.clone()));
+        let fetched_at = Utc::now();
+
+        let writers: Vec<_> = (1..=8)
+            .map(|i| {
+                let cache = cache.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        cache.store("LSD", fetched_at, &vec![i; i * 1000]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let (loaded_at, data) = cache.load::<Vec<usize>>("LSD").unwrap();
+        let leftovers = std::fs::read_dir(&dir).unwrap().count();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded_at, fetched_at);
+        assert!(data.iter().all(|&i| data.len() == i * 1000));
+        assert_eq!(leftovers, 1);
+    }
+}
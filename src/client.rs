@@ -0,0 +1,1514 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures::{future, stream, Stream, StreamExt};
+use graphql_client::GraphQLQuery;
+use reqwest::header::{HeaderMap, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::{Proxy, StatusCode, Url};
+use serde::de::DeserializeOwned;
+
+use crate::cache::{CacheOptions, DiskCache, Lookup, ResponseCache};
+use crate::error::{PwikiError, Result};
+use crate::limit::{RateLimit, RateLimiter, WaitMetrics};
+use tokio::time::{sleep, timeout_at, Instant};
+
+use crate::query::{
+    batch_query, effects_by_substance_query, substance_query, substances_by_class_query,
+    substances_by_effect_query, ConversionMode, EffectsBySubstanceQuery, IntoSubstance,
+    SubstanceQuery, SubstancesByClassQuery, SubstancesByEffectQuery,
+};
+use crate::retry::RetryPolicy;
+use crate::structure::{name_key, Effect, Substance};
+
+/// The public PsychonautWiki GraphQL endpoint.
+pub const DEFAULT_ENDPOINT: &str = "https://api.psychonautwiki.org/";
+
+/// Names looked up per request by [`PwikiClient::fetch_many`] when no batch
+/// size is configured.
+pub const DEFAULT_BATCH_SIZE: usize = 25;
+
+/// User agent sent when none is configured.
+pub const DEFAULT_USER_AGENT: &str = concat!("pwiki-api/", env!("CARGO_PKG_VERSION"));
+
+/// A reusable handle to a PsychonautWiki compatible GraphQL endpoint.
+///
+/// The underlying connection pool is shared between clones, so a single
+/// client should be built once and cloned into every task that needs it.
+#[derive(Debug, Clone)]
+pub struct PwikiClient {
+    http: reqwest::Client,
+    endpoint: Url,
+    conversion: ConversionMode,
+    cache: Option<Arc<ResponseCache>>,
+    disk_cache: Option<Arc<DiskCache>>,
+    retry: RetryPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    batch_size: usize,
+}
+
+impl PwikiClient {
+    /// Builds a client for [`DEFAULT_ENDPOINT`] with the default settings.
+    pub fn new() -> Self {
+        Self::builder()
+            .build()
+            .expect("default client configuration is valid")
+    }
+
+    pub fn builder() -> PwikiClientBuilder {
+        PwikiClientBuilder::new()
+    }
+
+    pub fn endpoint(&self) -> &Url {
+        &self.endpoint
+    }
+
+    fn convert<S: IntoSubstance>(
+        &self,
+        substances: impl IntoIterator<Item = S>,
+    ) -> Result<Vec<Substance>> {
+        substances
+            .into_iter()
+            .map(|i| Ok(i.into_substance(self.conversion)?))
+            .collect()
+    }
+
+    async fn post_query<Q: GraphQLQuery>(
+        &self,
+        variables: Q::Variables,
+    ) -> Result<(Q::ResponseData, Origin)> {
+        let request_body = Q::build_query(variables);
+        let json = serde_json::to_vec(&request_body)?;
+
+        let Some(cache) = &self.cache else {
+            let (data, _) = self.execute::<Q::ResponseData>(json).await?;
+            return Ok((data, Origin::Network(Utc::now())));
+        };
+
+        let key = ResponseCache::key(&request_body);
+        let (cached, stale) = match cache.get(&key) {
+            Lookup::Fresh(cached) => (cached, false),
+            Lookup::Stale { cached, revalidate } => {
+                if revalidate {
+                    self.revalidate(cache.clone(), key, json, |r| {
+                        decode::<Q::ResponseData>(r).map(drop)
+                    });
+                }
+                (cached, true)
+            }
+            Lookup::Miss => {
+                let (data, body) = self.execute::<Q::ResponseData>(json).await?;
+                let fetched_at = Utc::now();
+                cache.insert(key, body, fetched_at);
+
+                return Ok((data, Origin::Network(fetched_at)));
+            }
+        };
+
+        let data = decode::<Q::ResponseData>(&RawResponse::cached(cached.body))?;
+        let origin = Origin::Memory {
+            fetched_at: cached.fetched_at,
+            stale,
+        };
+        Ok((data, origin))
+    }
+
+    /// Sends a query, retrying transient failures according to the
+    /// client's [`RetryPolicy`].
+    async fn execute<T: DeserializeOwned>(&self, json: Vec<u8>) -> Result<(T, Arc<[u8]>)> {
+        let deadline = self.retry.deadline.map(|i| Instant::now() + i);
+        let mut attempt = 1;
+
+        loop {
+            let response = match deadline {
+                Some(deadline) => timeout_at(deadline, self.send(json.clone()))
+                    .await
+                    .unwrap_or(Err(PwikiError::DeadlineExceeded { attempts: attempt })),
+                None => self.send(json.clone()).await,
+            };
+
+            let error = match response.and_then(|r| Ok((decode::<T>(&r)?, r.body))) {
+                Err(e) if e.is_transient() && attempt < self.retry.max_attempts => e,
+                result => return result,
+            };
+
+            let retry_after = match &error {
+                PwikiError::Status { retry_after, .. } => *retry_after,
+                _ => None,
+            };
+            let Some(delay) = self.retry.delay(attempt, retry_after) else {
+                return Err(error);
+            };
+            if deadline.is_some_and(|i| Instant::now() + delay >= i) {
+                return Err(error);
+            }
+
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    async fn send(&self, json: Vec<u8>) -> Result<RawResponse> {
+        let _permit = match &self.limiter {
+            Some(limiter) => limiter.acquire().await,
+            None => None,
+        };
+
+        let res = self
+            .http
+            .post(self.endpoint.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(json)
+            .send()
+            .await?;
+        let status = res.status();
+        let retry_after = res
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|i| i.to_str().ok())
+            .and_then(parse_retry_after);
+        let body = res.bytes().await?;
+
+        Ok(RawResponse {
+            status,
+            retry_after,
+            body: Arc::from(&body[..]),
+        })
+    }
+
+    /// Refreshes a stale cache entry without holding up the caller. Only
+    /// responses that pass `validate` replace the cached one.
+    fn revalidate(
+        &self,
+        cache: Arc<ResponseCache>,
+        key: String,
+        json: Vec<u8>,
+        validate: fn(&RawResponse) -> Result<()>,
+    ) {
+        let client = self.clone();
+
+        tokio::spawn(async move {
+            match client.send(json).await {
+                Ok(r) if validate(&r).is_ok() => cache.insert(key, r.body, Utc::now()),
+                _ => cache.revalidation_failed(&key),
+            }
+        });
+    }
+
+    /// How long requests have waited for the client's [`RateLimit`], shared
+    /// between clones. `None` if no limit is configured.
+    pub fn wait_metrics(&self) -> Option<WaitMetrics> {
+        self.limiter.as_ref().map(|i| i.metrics())
+    }
+
+    /// Drops the cached response for one query, e.g.
+    /// `client.invalidate::<SubstanceQuery>(substance_query::Variables { .. })`.
+    pub fn invalidate<Q: GraphQLQuery>(&self, variables: Q::Variables) {
+        if let Some(cache) = &self.cache {
+            cache.remove(&ResponseCache::key(&Q::build_query(variables)));
+        }
+    }
+
+    /// Drops every cached response.
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+    }
+
+    pub async fn substance_data(&self, substance: impl AsRef<str>) -> Result<Vec<Substance>> {
+        Ok(self.fetch_substance_data(substance).await?.data)
+    }
+
+    /// The substance named exactly `name`, or with `name` among its common
+    /// names, ignoring case and punctuation.
+    ///
+    /// The API matches names loosely, so a lookup may return several
+    /// substances or none that is actually called `name`. A substance whose
+    /// name matches is preferred over one with a matching common name. If the
+    /// API returns nothing the error is [`PwikiError::NotFound`], and if no
+    /// substance matches, or several share the common name, it is
+    /// [`PwikiError::Ambiguous`] listing the candidates.
+    pub async fn resolve(&self, name: impl AsRef<str>) -> Result<Substance> {
+        let name = name.as_ref();
+        let mut data = self.substance_data(name).await?;
+        if data.is_empty() {
+            return Err(PwikiError::NotFound {
+                name: name.to_string(),
+            });
+        }
+
+        let key = name_key(name);
+        if let Some(i) = data.iter().position(|i| name_key(&i.name) == key) {
+            return Ok(data.swap_remove(i));
+        }
+
+        let mut matching: Vec<_> = data.iter().filter(|i| i.is_named(name)).collect();
+        if matching.len() == 1 {
+            return Ok(matching.remove(0).clone());
+        }
+        if matching.is_empty() {
+            matching = data.iter().collect();
+        }
+
+        Err(PwikiError::Ambiguous {
+            name: name.to_string(),
+            candidates: matching.into_iter().map(|i| i.name.clone()).collect(),
+        })
+    }
+
+    /// Like [`PwikiClient::substance_data`], but with a configured
+    /// [disk cache](PwikiClientBuilder::disk_cache) the last successful
+    /// response is served instead of failing when the endpoint is down or
+    /// unreachable.
+    pub async fn fetch_substance_data(
+        &self,
+        substance: impl AsRef<str>,
+    ) -> Result<Fetched<Vec<Substance>>> {
+        let name = substance.as_ref();
+        let result = self
+            .post_query::<SubstanceQuery>(substance_query::Variables {
+                substance: name.into(),
+            })
+            .await;
+
+        let (r, fetched_at, stale) = match (result, &self.disk_cache) {
+            (Ok((r, Origin::Network(fetched_at))), disk_cache) => {
+                if let Some(disk_cache) = disk_cache.clone() {
+                    // the fallback copy is best effort, the fresh data is
+                    // still returned if it cannot be written
+                    let (name, data) = (name.to_string(), r.clone());
+                    let _ = tokio::task::spawn_blocking(move || {
+                        disk_cache.store(&name, fetched_at, &data)
+                    })
+                    .await;
+                }
+                (r, fetched_at, None)
+            }
+            // already on disk when it was fetched
+            (Ok((r, Origin::Memory { fetched_at, stale })), _) => {
+                (r, fetched_at, stale.then_some(Staleness::Revalidating))
+            }
+            // the endpoint is unreachable or misbehaving, as opposed to the
+            // query or its data being invalid
+            (Err(e), Some(disk_cache))
+                if matches!(
+                    e,
+                    PwikiError::Transport(_)
+                        | PwikiError::Status { .. }
+                        | PwikiError::Decode(_)
+                        | PwikiError::DeadlineExceeded { .. }
+                ) =>
+            {
+                let (disk_cache, name) = (disk_cache.clone(), name.to_string());
+                match tokio::task::spawn_blocking(move || disk_cache.load(&name)).await {
+                    Ok(Some((fetched_at, r))) => (r, fetched_at, Some(Staleness::Unavailable(e))),
+                    _ => return Err(e),
+                }
+            }
+            (Err(e), _) => return Err(e),
+        };
+        let s = r.substances.ok_or(PwikiError::MissingData("substances"))?;
+
+        Ok(Fetched {
+            data: self.convert(s.into_iter().flatten())?,
+            fetched_at,
+            stale,
+        })
+    }
+
+    /// Looks up several substances with one request per
+    /// [batch](PwikiClientBuilder::batch_size) of names, instead of one
+    /// request per name as with [`PwikiClient::substance_data`].
+    ///
+    /// Duplicate names are looked up once. Names the API returns nothing for
+    /// are listed in [`BatchLookup::missing`].
+    pub async fn fetch_many<S: AsRef<str>>(
+        &self,
+        names: impl IntoIterator<Item = S>,
+    ) -> Result<BatchLookup> {
+        let mut requested: Vec<String> = Vec::new();
+        for name in names {
+            let name = name.as_ref();
+            if !requested.iter().any(|i| i == name) {
+                requested.push(name.to_string());
+            }
+        }
+
+        let batches = requested
+            .chunks(self.batch_size)
+            .map(|names| self.fetch_batch(names));
+        let results = future::try_join_all(batches).await?;
+
+        let mut lookup = BatchLookup::default();
+        for (name, substances) in requested.into_iter().zip(results.into_iter().flatten()) {
+            if substances.is_empty() {
+                lookup.missing.push(name);
+            } else {
+                lookup.found.push((name, substances));
+            }
+        }
+
+        Ok(lookup)
+    }
+
+    async fn fetch_batch(&self, names: &[String]) -> Result<Vec<Vec<Substance>>> {
+        let variables: serde_json::Map<_, _> = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (format!("q{i}"), name.as_str().into()))
+            .collect();
+        let request_body = serde_json::json!({
+            "variables": variables,
+            "query": batch_query(names.len()),
+            "operationName": "SubstancesBatchQuery",
+        });
+
+        let (mut data, _) = self
+            .execute::<HashMap<String, Option<Vec<Option<substance_query::SubstanceFields>>>>>(
+                serde_json::to_vec(&request_body)?,
+            )
+            .await?;
+
+        (0..names.len())
+            .map(|i| {
+                let s = data.remove(&format!("s{i}")).flatten().unwrap_or_default();
+                self.convert(s.into_iter().flatten())
+            })
+            .collect()
+    }
+
+    /// Substances that are documented to produce `effect`, e.g. "Euphoria".
+    pub async fn substances_by_effect(
+        &self,
+        effect: impl AsRef<str>,
+        page: Page,
+    ) -> Result<Vec<Substance>> {
+        let (r, _) = self
+            .post_query::<SubstancesByEffectQuery>(substances_by_effect_query::Variables {
+                effect: effect.as_ref().into(),
+                limit: Some(page.limit),
+                offset: Some(page.offset),
+            })
+            .await?;
+        let s = r
+            .substances_by_effect
+            .ok_or(PwikiError::MissingData("substances_by_effect"))?;
+
+        self.convert(s.into_iter().flatten())
+    }
+
+    /// Substances matching every class set in `filter`.
+    pub async fn substances_by_class(
+        &self,
+        filter: &ClassFilter,
+        page: Page,
+    ) -> Result<Vec<Substance>> {
+        let s = self.substances_by_class_raw(filter, page).await?;
+
+        self.convert(s)
+    }
+
+    async fn substances_by_class_raw(
+        &self,
+        filter: &ClassFilter,
+        page: Page,
+    ) -> Result<Vec<substances_by_class_query::SubstanceFields>> {
+        let (r, _) = self
+            .post_query::<SubstancesByClassQuery>(substances_by_class_query::Variables {
+                chemical_class: filter.chemical.clone(),
+                psychoactive_class: filter.psychoactive.clone(),
+                limit: Some(page.limit),
+                offset: Some(page.offset),
+            })
+            .await?;
+        let s = r.substances.ok_or(PwikiError::MissingData("substances"))?;
+
+        Ok(s.into_iter().flatten().collect())
+    }
+
+    /// Walks every page of the catalog, optionally restricted to a class,
+    /// until the API returns a short page.
+    ///
+    /// Up to `options.concurrency` pages are requested at once, so a few
+    /// empty pages past the end of the catalog may be fetched and discarded.
+    /// Substances are yielded in catalog order. The stream ends after the
+    /// first error.
+    pub fn substance_catalog(
+        &self,
+        options: CatalogOptions,
+    ) -> impl Stream<Item = Result<Substance>> {
+        let client = self.clone();
+
+        self.catalog_pages(options).flat_map(move |page| {
+            stream::iter(match page.and_then(|s| client.convert(s)) {
+                Ok(substances) => substances.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            })
+        })
+    }
+
+    /// The unconverted pages behind [`PwikiClient::substance_catalog`].
+    pub(crate) fn catalog_pages(
+        &self,
+        options: CatalogOptions,
+    ) -> impl Stream<Item = Result<Vec<substances_by_class_query::SubstanceFields>>> {
+        let client = self.clone();
+        let CatalogOptions {
+            page_size,
+            concurrency,
+            filter,
+        } = options;
+        let page_size = page_size.max(1);
+
+        stream::iter((0..).map(move |i| Page::new(page_size, i * page_size)))
+            .map(move |page| {
+                let client = client.clone();
+                let filter = filter.clone();
+                async move { client.substances_by_class_raw(&filter, page).await }
+            })
+            .buffered(concurrency.max(1))
+            .scan(false, move |done, page| {
+                if *done {
+                    return future::ready(None);
+                }
+
+                *done = match &page {
+                    Ok(substances) => (substances.len() as i64) < page_size,
+                    Err(_) => true,
+                };
+
+                future::ready(Some(page))
+            })
+    }
+
+    /// Effects documented for `substance`.
+    pub async fn effects_by_substance(
+        &self,
+        substance: impl AsRef<str>,
+        page: Page,
+    ) -> Result<Vec<Effect>> {
+        let (r, _) = self
+            .post_query::<EffectsBySubstanceQuery>(effects_by_substance_query::Variables {
+                substance: substance.as_ref().into(),
+                limit: Some(page.limit),
+                offset: Some(page.offset),
+            })
+            .await?;
+        let e = r
+            .effects_by_substance
+            .ok_or(PwikiError::MissingData("effects_by_substance"))?;
+
+        Ok(e.into_iter().flatten().map(|i| i.into()).collect())
+    }
+}
+
+impl Default for PwikiClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Where the data returned by `post_query` came from.
+#[derive(Debug, Clone, Copy)]
+enum Origin {
+    /// Returned by the endpoint at the given time.
+    Network(DateTime<Utc>),
+    /// Served from the in-memory cache.
+    Memory {
+        fetched_at: DateTime<Utc>,
+        /// Past the ttl and being revalidated.
+        stale: bool,
+    },
+}
+
+/// A response as received, before it is decoded.
+struct RawResponse {
+    status: StatusCode,
+    retry_after: Option<Duration>,
+    body: Arc<[u8]>,
+}
+
+impl RawResponse {
+    fn cached(body: Arc<[u8]>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            retry_after: None,
+            body,
+        }
+    }
+
+    fn status_error(&self) -> PwikiError {
+        PwikiError::Status {
+            status: self.status,
+            body: String::from_utf8_lossy(&self.body).into_owned(),
+            retry_after: self.retry_after,
+        }
+    }
+}
+
+fn decode<T: DeserializeOwned>(response: &RawResponse) -> Result<T> {
+    let response_body: graphql_client::Response<T> = match serde_json::from_slice(&response.body) {
+        Ok(r) => r,
+        Err(_) if !response.status.is_success() => return Err(response.status_error()),
+        Err(e) => return Err(e.into()),
+    };
+
+    // graphql servers commonly pair validation errors with a 4xx status,
+    // in which case the errors are more useful than the status alone
+    if let Some(e) = response_body.errors.filter(|e| !e.is_empty()) {
+        return Err(PwikiError::GraphQl(e));
+    }
+
+    if !response.status.is_success() {
+        return Err(response.status_error());
+    }
+
+    response_body.data.ok_or(PwikiError::MissingData("data"))
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse() {
+        return Some(Duration::from_secs(secs));
+    }
+
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&Utc) - Utc::now()).to_std().ok()
+}
+
+/// The results of [`PwikiClient::fetch_many`], in the order the names were
+/// requested.
+#[derive(Debug, Clone, Default)]
+pub struct BatchLookup {
+    /// The substances returned for each name that matched anything.
+    pub found: Vec<(String, Vec<Substance>)>,
+    /// Names that matched nothing.
+    pub missing: Vec<String>,
+}
+
+impl BatchLookup {
+    pub fn get(&self, name: &str) -> Option<&[Substance]> {
+        self.found
+            .iter()
+            .find(|(i, _)| i == name)
+            .map(|(_, substances)| substances.as_slice())
+    }
+}
+
+/// Data returned by the client, possibly from one of its caches.
+#[derive(Debug)]
+pub struct Fetched<T> {
+    pub data: T,
+    /// When the data was returned by the endpoint.
+    pub fetched_at: DateTime<Utc>,
+    /// Why an outdated copy was served instead of a fresh response.
+    pub stale: Option<Staleness>,
+}
+
+/// Why [`Fetched`] data is outdated.
+#[derive(Debug)]
+pub enum Staleness {
+    /// The in-memory copy is past its ttl and is being refreshed in the
+    /// background, see [`CacheOptions::stale_while_revalidate`].
+    Revalidating,
+    /// The endpoint failed, so the on-disk copy was served.
+    Unavailable(PwikiError),
+}
+
+impl<T> Fetched<T> {
+    pub fn is_stale(&self) -> bool {
+        self.stale.is_some()
+    }
+}
+
+/// A `limit`/`offset` window into a list query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    pub limit: i64,
+    pub offset: i64,
+}
+
+impl Page {
+    pub fn new(limit: i64, offset: i64) -> Self {
+        Self { limit, offset }
+    }
+
+    /// The page directly after this one.
+    pub fn next(self) -> Self {
+        Self {
+            offset: self.offset + self.limit,
+            ..self
+        }
+    }
+}
+
+impl Default for Page {
+    /// Matches the API's own default for the effect queries.
+    fn default() -> Self {
+        Self::new(50, 0)
+    }
+}
+
+/// Settings for [`PwikiClient::substance_catalog`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogOptions {
+    /// Substances requested per page.
+    pub page_size: i64,
+    /// Maximum number of pages requested at once.
+    pub concurrency: usize,
+    pub filter: ClassFilter,
+}
+
+impl Default for CatalogOptions {
+    fn default() -> Self {
+        Self {
+            page_size: 100,
+            concurrency: 4,
+            filter: ClassFilter::default(),
+        }
+    }
+}
+
+/// Restricts a substance search to a chemical and/or psychoactive class,
+/// e.g. "Tryptamines" or "Dissociatives". Unset fields match everything.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ClassFilter {
+    pub chemical: Option<String>,
+    pub psychoactive: Option<String>,
+}
+
+impl ClassFilter {
+    pub fn chemical(class: impl Into<String>) -> Self {
+        Self {
+            chemical: Some(class.into()),
+            psychoactive: None,
+        }
+    }
+
+    pub fn psychoactive(class: impl Into<String>) -> Self {
+        Self {
+            chemical: None,
+            psychoactive: Some(class.into()),
+        }
+    }
+
+    pub fn and_chemical(mut self, class: impl Into<String>) -> Self {
+        self.chemical = Some(class.into());
+        self
+    }
+
+    pub fn and_psychoactive(mut self, class: impl Into<String>) -> Self {
+        self.psychoactive = Some(class.into());
+        self
+    }
+}
+
+/// Configuration for a [`PwikiClient`].
+#[derive(Debug)]
+pub struct PwikiClientBuilder {
+    endpoint: String,
+    conversion: ConversionMode,
+    user_agent: String,
+    headers: HeaderMap,
+    timeout: Option<Duration>,
+    proxy: Option<Proxy>,
+    cache: Option<CacheOptions>,
+    disk_cache: Option<PathBuf>,
+    retry: RetryPolicy,
+    rate_limit: Option<RateLimit>,
+    batch_size: usize,
+}
+
+impl PwikiClientBuilder {
+    pub fn new() -> Self {
+        Self {
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            conversion: ConversionMode::default(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            headers: HeaderMap::new(),
+            timeout: None,
+            proxy: None,
+            cache: None,
+            disk_cache: None,
+            retry: RetryPolicy::none(),
+            rate_limit: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+
+    /// Sets the GraphQL endpoint, e.g. a local mirror of the wiki API.
+    pub fn endpoint(mut self, endpoint: impl Into<String>) -> Self {
+        self.endpoint = endpoint.into();
+        self
+    }
+
+    /// How substances with uninterpretable duration data are handled,
+    /// [`ConversionMode::Lenient`] by default.
+    pub fn conversion_mode(mut self, mode: ConversionMode) -> Self {
+        self.conversion = mode;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Headers sent with every request, in addition to the user agent.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers = headers;
+        self
+    }
+
+    /// Total time allowed for a single request, from connecting until the
+    /// response body has been read.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn proxy(mut self, proxy: Proxy) -> Self {
+        self.proxy = Some(proxy);
+        self
+    }
+
+    /// Caches successful responses in memory, see [`CacheOptions`].
+    pub fn cache(mut self, options: CacheOptions) -> Self {
+        self.cache = Some(options);
+        self
+    }
+
+    /// How transient failures are retried. Requests are sent once unless a
+    /// policy is set.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Throttles requests, including retries, across the client and all of
+    /// its clones.
+    pub fn rate_limit(mut self, limit: RateLimit) -> Self {
+        self.rate_limit = Some(limit);
+        self
+    }
+
+    /// Names looked up per request by [`PwikiClient::fetch_many`],
+    /// [`DEFAULT_BATCH_SIZE`] by default.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Keeps the last successful response for each substance looked up with
+    /// [`PwikiClient::substance_data`] in `dir`, to be served when the
+    /// endpoint fails. The directory is created when first written to.
+    pub fn disk_cache(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.disk_cache = Some(dir.into());
+        self
+    }
+
+    pub fn build(self) -> Result<PwikiClient> {
+        let endpoint = Url::parse(&self.endpoint).map_err(|e| PwikiError::InvalidEndpoint {
+            endpoint: self.endpoint.clone(),
+            reason: e.to_string(),
+        })?;
+
+        let mut http = reqwest::Client::builder()
+            .user_agent(self.user_agent)
+            .default_headers(self.headers);
+
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+
+        if let Some(proxy) = self.proxy {
+            http = http.proxy(proxy);
+        }
+
+        self.retry.validate()?;
+        let limiter = self.rate_limit.map(RateLimiter::new).transpose()?;
+
+        Ok(PwikiClient {
+            http: http.build()?,
+            endpoint,
+            conversion: self.conversion,
+            cache: self.cache.map(|i| Arc::new(ResponseCache::new(i))),
+            disk_cache: self.disk_cache.map(|i| Arc::new(DiskCache::new(i))),
+            retry: self.retry,
+            limiter: limiter.map(Arc::new),
+            batch_size: self.batch_size,
+        })
+    }
+}
+
+impl Default for PwikiClientBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use wiremock::matchers::{body_partial_json, header, method};
+    use wiremock::{Mock, ResponseTemplate};
+
+    use super::*;
+    use crate::test_util::{exclusive_mock_endpoint, mock_endpoint, LSD_FIXTURE};
+
+    #[tokio::test]
+    async fn test_query() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+
+        let data = client.substance_data("LSD").await.unwrap();
+        assert_eq!(data.len(), 1);
+        assert_eq!(data[0].name, "LSD");
+        assert!(data[0].class.contains("psychedelics"));
+        assert!(data[0].common_names.contains(&"Acid".to_string()));
+        assert_eq!(
+            data[0].tolerance.as_ref().unwrap().zero.as_deref(),
+            Some("14 days")
+        );
+        assert!(data[0].summary.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_class_filter() {
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "operationName": "SubstancesByClassQuery",
+                "variables": {
+                    "chemicalClass": "Lysergamides",
+                    "psychoactiveClass": "Psychedelics",
+                    "limit": 50,
+                    "offset": 0,
+                },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(LSD_FIXTURE, "application/json"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+        let filter = ClassFilter::psychoactive("Psychedelics").and_chemical("Lysergamides");
+
+        let data = client
+            .substances_by_class(&filter, Page::default())
+            .await
+            .unwrap();
+        assert_eq!(data[0].class.chemical, vec!["Lysergamides".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_client_settings() {
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(header("user-agent", "dose-graph/1.0"))
+            .and(header("x-api-key", "secret"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(r#"{"data":{"substances":[]}}"#, "application/json"),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-api-key", "secret".parse().unwrap());
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .user_agent("dose-graph/1.0")
+            .default_headers(headers)
+            .timeout(Duration::from_secs(5))
+            .build()
+            .unwrap();
+
+        assert!(client.substance_data("LSD").await.unwrap().is_empty());
+        assert!(client
+            .clone()
+            .substance_data("DMT")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_catalog() {
+        let server = wiremock::MockServer::start().await;
+        let page = |names: &[&str]| {
+            let substances: Vec<_> = names
+                .iter()
+                .map(|name| serde_json::json!({ "name": name }))
+                .collect();
+            ResponseTemplate::new(200)
+                .set_body_json(serde_json::json!({ "data": { "substances": substances } }))
+        };
+        for (offset, names) in [
+            (0, &["2C-B", "DMT"][..]),
+            (2, &["LSD", "MDMA"]),
+            (4, &["THC"]),
+        ] {
+            Mock::given(method("POST"))
+                .and(body_partial_json(serde_json::json!({
+                    "variables": { "limit": 2, "offset": offset },
+                })))
+                .respond_with(page(names))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        Mock::given(method("POST"))
+            .respond_with(page(&[]))
+            .with_priority(10)
+            .mount(&server)
+            .await;
+
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+        let names: Vec<_> = client
+            .substance_catalog(CatalogOptions {
+                page_size: 2,
+                concurrency: 3,
+                ..Default::default()
+            })
+            .map(|s| s.unwrap().name)
+            .collect()
+            .await;
+
+        assert_eq!(names, ["2C-B", "DMT", "LSD", "MDMA", "THC"]);
+    }
+
+    #[tokio::test]
+    async fn test_effects() {
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "operationName": "EffectsBySubstanceQuery",
+                "variables": { "substance": "LSD", "limit": 50, "offset": 0 },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                r#"{"data":{"effects_by_substance":[
+                    {"name":"Euphoria","url":"https://psychonautwiki.org/wiki/Euphoria"},
+                    null
+                ]}}"#,
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "operationName": "SubstancesByEffectQuery",
+                "variables": { "effect": "Euphoria", "limit": 10, "offset": 20 },
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_raw(
+                LSD_FIXTURE.replace("\"substances\"", "\"substances_by_effect\""),
+                "application/json",
+            ))
+            .mount(&server)
+            .await;
+
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+
+        let effects = client
+            .effects_by_substance("LSD", Page::default())
+            .await
+            .unwrap();
+        assert_eq!(
+            effects,
+            vec![Effect {
+                name: "Euphoria".to_string(),
+                url: Some("https://psychonautwiki.org/wiki/Euphoria".to_string()),
+            }]
+        );
+
+        let substances = client
+            .substances_by_effect("Euphoria", Page::new(10, 10).next())
+            .await
+            .unwrap();
+        assert_eq!(substances[0].name, "LSD");
+    }
+
+    #[tokio::test]
+    async fn test_cache() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .cache(CacheOptions {
+                max_entries: 2,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let requests = || async { server.received_requests().await.unwrap().len() };
+
+        client.substance_data("LSD").await.unwrap();
+        client.clone().substance_data("LSD").await.unwrap();
+        assert_eq!(requests().await, 1);
+
+        client.invalidate::<SubstanceQuery>(substance_query::Variables {
+            substance: "LSD".into(),
+        });
+        client.substance_data("LSD").await.unwrap();
+        assert_eq!(requests().await, 2);
+
+        // evicts LSD, the oldest entry
+        client.substance_data("DMT").await.unwrap();
+        client.substance_data("MDMA").await.unwrap();
+        client.substance_data("LSD").await.unwrap();
+        assert_eq!(requests().await, 5);
+
+        client.clear_cache();
+        client.substance_data("LSD").await.unwrap();
+        assert_eq!(requests().await, 6);
+    }
+
+    #[tokio::test]
+    async fn test_stale_while_revalidate() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .cache(CacheOptions {
+                ttl: Duration::ZERO,
+                stale_while_revalidate: Duration::from_secs(60),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let requests = || async { server.received_requests().await.unwrap().len() };
+
+        client.substance_data("LSD").await.unwrap();
+        assert_eq!(requests().await, 1);
+
+        // served from the cache, refreshed in the background
+        let data = client.substance_data("LSD").await.unwrap();
+        assert_eq!(data[0].name, "LSD");
+
+        for _ in 0..50 {
+            if requests().await > 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(requests().await, 2);
+    }
+
+    #[tokio::test]
+    async fn test_disk_cache() {
+        let dir = std::env::temp_dir().join(format!("pwiki-disk-cache-{}", std::process::id()));
+        let server = exclusive_mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .disk_cache(&dir)
+            .build()
+            .unwrap();
+
+        let fresh = client.fetch_substance_data("LSD").await.unwrap();
+        assert!(!fresh.is_stale());
+
+        drop(server);
+        let cached = client.fetch_substance_data("LSD").await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(
+            cached.stale,
+            Some(Staleness::Unavailable(PwikiError::Transport(_)))
+        ));
+        assert_eq!(cached.fetched_at, fresh.fetched_at);
+        assert_eq!(cached.data[0].name, "LSD");
+        assert!(client.substance_data("DMT").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_memory_and_disk_cache() {
+        let dir = std::env::temp_dir().join(format!("pwiki-both-caches-{}", std::process::id()));
+        let server = exclusive_mock_endpoint(LSD_FIXTURE).await;
+        let requests = || async { server.received_requests().await.unwrap().len() };
+        let builder = || {
+            PwikiClient::builder()
+                .endpoint(server.uri())
+                .disk_cache(&dir)
+        };
+
+        let client = builder().cache(CacheOptions::default()).build().unwrap();
+        let fresh = client.fetch_substance_data("LSD").await.unwrap();
+        let cached = client.fetch_substance_data("LSD").await.unwrap();
+        assert!(!cached.is_stale());
+        assert_eq!(cached.fetched_at, fresh.fetched_at);
+
+        let client = builder()
+            .cache(CacheOptions {
+                ttl: Duration::ZERO,
+                stale_while_revalidate: Duration::from_secs(60),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+        let first = client.fetch_substance_data("LSD").await.unwrap();
+        let stale = client.fetch_substance_data("LSD").await.unwrap();
+        assert!(matches!(stale.stale, Some(Staleness::Revalidating)));
+        assert_eq!(stale.fetched_at, first.fetched_at);
+        for _ in 0..50 {
+            if requests().await > 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(requests().await, 3);
+
+        // only the network response was written to disk, not the cache hit
+        // or its background refresh
+        drop(server);
+        client.clear_cache();
+        let offline = client.fetch_substance_data("LSD").await.unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert!(matches!(offline.stale, Some(Staleness::Unavailable(_))));
+        assert_eq!(offline.fetched_at, first.fetched_at);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .with_priority(1)
+            .mount(&server)
+            .await;
+
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(1),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .retry(policy.clone())
+            .build()
+            .unwrap();
+
+        assert_eq!(client.substance_data("LSD").await.unwrap()[0].name, "LSD");
+        assert_eq!(server.received_requests().await.unwrap().len(), 3);
+
+        // waiting as long as the endpoint asks would miss the deadline
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(429).insert_header("retry-after", "8"))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .retry(RetryPolicy {
+                deadline: Some(Duration::from_secs(5)),
+                ..policy.clone()
+            })
+            .build()
+            .unwrap();
+
+        match client.substance_data("LSD").await.unwrap_err() {
+            PwikiError::Status { retry_after, .. } => {
+                assert_eq!(retry_after, Some(Duration::from_secs(8)))
+            }
+            e => panic!("unexpected error: {e:?}"),
+        }
+
+        // or with no deadline, wait longer than max_delay
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(503).insert_header("retry-after", "86400"))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .retry(policy.clone())
+            .build()
+            .unwrap();
+
+        let err = tokio::time::timeout(Duration::from_secs(5), client.substance_data("LSD"))
+            .await
+            .expect("gave up instead of sleeping")
+            .unwrap_err();
+        assert!(matches!(err, PwikiError::Status { .. }));
+        assert_eq!(server.received_requests().await.unwrap().len(), 1);
+
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        Mock::given(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(5)))
+            .with_priority(1)
+            .mount(&server)
+            .await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .retry(RetryPolicy {
+                deadline: Some(Duration::from_millis(50)),
+                ..policy
+            })
+            .build()
+            .unwrap();
+
+        let err = client.substance_data("LSD").await.unwrap_err();
+        assert!(matches!(err, PwikiError::DeadlineExceeded { attempts: 1 }));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .rate_limit(RateLimit {
+                requests_per_second: Some(20.0),
+                burst: 2,
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let lookups = ["LSD", "DMT", "MDMA", "2C-B"].map(|i| client.substance_data(i));
+        for result in future::join_all(lookups).await {
+            result.unwrap();
+        }
+        // two requests go out right away, the others 50ms apart
+        assert!(started.elapsed() >= Duration::from_millis(100));
+
+        let metrics = client.wait_metrics().unwrap();
+        assert_eq!((metrics.requests, metrics.delayed), (4, 2));
+        assert!(metrics.max_wait >= Duration::from_millis(95));
+
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_raw(LSD_FIXTURE, "application/json")
+                    .set_delay(Duration::from_millis(50)),
+            )
+            .mount(&server)
+            .await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .rate_limit(RateLimit {
+                max_in_flight: Some(1),
+                ..Default::default()
+            })
+            .build()
+            .unwrap();
+
+        let started = std::time::Instant::now();
+        let (a, b, c) = future::join3(
+            client.substance_data("LSD"),
+            client.clone().substance_data("DMT"),
+            client.substance_data("MDMA"),
+        )
+        .await;
+        a.and(b).and(c).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(150));
+        assert_eq!(client.wait_metrics().unwrap().delayed, 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_many() {
+        let fixture: serde_json::Value = serde_json::from_str(LSD_FIXTURE).unwrap();
+        let lsd = &fixture["data"]["substances"][0];
+
+        let server = wiremock::MockServer::start().await;
+        for (variables, data) in [
+            (
+                serde_json::json!({ "q0": "LSD", "q1": "Nothing" }),
+                serde_json::json!({ "s0": [lsd], "s1": [] }),
+            ),
+            (
+                serde_json::json!({ "q0": "Acid" }),
+                serde_json::json!({ "s0": [lsd] }),
+            ),
+        ] {
+            Mock::given(method("POST"))
+                .and(body_partial_json(serde_json::json!({
+                    "operationName": "SubstancesBatchQuery",
+                    "variables": variables,
+                })))
+                .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                    "data": data,
+                })))
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .batch_size(2)
+            .build()
+            .unwrap();
+        let lookup = client
+            .fetch_many(["LSD", "Nothing", "LSD", "Acid"])
+            .await
+            .unwrap();
+
+        assert_eq!(lookup.missing, ["Nothing"]);
+        assert_eq!(lookup.found.len(), 2);
+        assert_eq!(lookup.get("LSD").unwrap()[0].name, "LSD");
+        assert_eq!(lookup.get("Acid").unwrap()[0].name, "LSD");
+
+        let queries: Vec<String> = server
+            .received_requests()
+            .await
+            .unwrap()
+            .iter()
+            .map(|i| i.body_json::<serde_json::Value>().unwrap()["query"].to_string())
+            .collect();
+        assert!(queries
+            .iter()
+            .any(|i| i.contains("s1: substances(query: $q1)")));
+    }
+
+    #[tokio::test]
+    async fn test_resolve() {
+        let fixture: serde_json::Value = serde_json::from_str(LSD_FIXTURE).unwrap();
+        let lsd = &fixture["data"]["substances"][0];
+        let server = mock_endpoint(
+            &serde_json::json!({ "data": { "substances": [
+                { "name": "1P-LSD", "commonNames": ["1P-LSD", "Acid"] },
+                lsd,
+                { "name": "ALD-52", "commonNames": ["Acid"] },
+            ] } })
+            .to_string(),
+        )
+        .await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+
+        assert_eq!(client.resolve("lsd").await.unwrap().name, "LSD");
+        assert_eq!(client.resolve("lsd 25").await.unwrap().name, "LSD");
+        assert_eq!(client.resolve("1p lsd").await.unwrap().name, "1P-LSD");
+
+        match client.resolve("Acid").await.unwrap_err() {
+            PwikiError::Ambiguous { candidates, .. } => {
+                assert_eq!(candidates, ["1P-LSD", "LSD", "ALD-52"])
+            }
+            e => panic!("unexpected error: {e:?}"),
+        }
+        match client.resolve("Lucid").await.unwrap_err() {
+            PwikiError::Ambiguous { candidates, .. } => assert_eq!(candidates.len(), 3),
+            e => panic!("unexpected error: {e:?}"),
+        }
+
+        let server = mock_endpoint(r#"{ "data": { "substances": [] } }"#).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+        match client.resolve("Nonexistent").await.unwrap_err() {
+            PwikiError::NotFound { name } => assert_eq!(name, "Nonexistent"),
+            e => panic!("unexpected error: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_endpoint() {
+        let err = PwikiClient::builder()
+            .endpoint("not a url")
+            .build()
+            .unwrap_err();
+        assert!(matches!(err, PwikiError::InvalidEndpoint { .. }));
+    }
+
+    async fn query_with_response(response: ResponseTemplate) -> Result<Vec<Substance>> {
+        let server = wiremock::MockServer::start().await;
+        Mock::given(method("POST"))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+        client.substance_data("LSD").await
+    }
+
+    #[tokio::test]
+    async fn test_status_error() {
+        let err = query_with_response(ResponseTemplate::new(503).set_body_string("down"))
+            .await
+            .unwrap_err();
+
+        match &err {
+            PwikiError::Status {
+                status,
+                body,
+                retry_after,
+            } => {
+                assert_eq!(*status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+                assert_eq!(body, "down");
+                assert_eq!(*retry_after, None);
+            }
+            e => panic!("unexpected error: {e:?}"),
+        }
+        assert!(err.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_decode_error() {
+        let err = query_with_response(ResponseTemplate::new(200).set_body_string("<html>"))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, PwikiError::Decode(_)));
+        assert!(!err.is_transient());
+    }
+
+    #[tokio::test]
+    async fn test_graphql_error() {
+        let body = r#"{
+            "data": null,
+            "errors": [{
+                "message": "Cannot query field \"nme\"",
+                "locations": [{ "line": 3, "column": 9 }],
+                "path": ["substances", 0, "nme"],
+                "extensions": { "code": "GRAPHQL_VALIDATION_FAILED" }
+            }]
+        }"#;
+        let err =
+            query_with_response(ResponseTemplate::new(400).set_body_raw(body, "application/json"))
+                .await
+                .unwrap_err();
+
+        let PwikiError::GraphQl(errors) = err else {
+            panic!("expected graphql error");
+        };
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].locations.as_ref().unwrap()[0].line, 3);
+        assert_eq!(errors[0].path.as_ref().unwrap().len(), 3);
+        assert!(errors[0].extensions.as_ref().unwrap().contains_key("code"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_data() {
+        let err = query_with_response(
+            ResponseTemplate::new(200)
+                .set_body_raw(r#"{"data":{"substances":null}}"#, "application/json"),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(matches!(err, PwikiError::MissingData("substances")));
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::client::ClassFilter;
+use crate::error::{PwikiError, Result};
+use crate::query::{substance_query, ConversionMode, IntoSubstance};
+use crate::snapshot::{SnapshotMetadata, SNAPSHOT_SCHEMA_VERSION};
+use crate::structure::{name_key, Substance};
+
+/// An in-memory catalog of substances that answers lookups without network
+/// access.
+///
+/// Snapshots use the same shape as a response to the `SubstanceQuery`
+/// operation, i.e. `{ "data": { "substances": [...] } }`, optionally with
+/// the [`SnapshotMetadata`] written by [`Snapshot`](crate::snapshot::Snapshot).
+#[derive(Debug, Clone, Default)]
+pub struct SubstanceDatabase {
+    substances: Vec<Substance>,
+    /// [`name_key`]s of names and common names, pointing into `substances`.
+    names: HashMap<String, usize>,
+    metadata: Option<SnapshotMetadata>,
+}
+
+impl SubstanceDatabase {
+    pub fn new(substances: Vec<Substance>) -> Self {
+        let mut names = HashMap::new();
+
+        // names take priority over common names shared with other substances
+        for (i, substance) in substances.iter().enumerate() {
+            names.insert(name_key(&substance.name), i);
+        }
+        for (i, substance) in substances.iter().enumerate() {
+            for common_name in &substance.common_names {
+                names.entry(name_key(common_name)).or_insert(i);
+            }
+        }
+
+        Self {
+            substances,
+            names,
+            metadata: None,
+        }
+    }
+
+    /// Reads a snapshot file, see [`SubstanceDatabase::from_json`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let json = std::fs::read(path)?;
+        Self::from_json(&json, ConversionMode::default())
+    }
+
+    /// Parses a snapshot in the shape of a `SubstanceQuery` response.
+    pub fn from_json(json: &[u8], mode: ConversionMode) -> Result<Self> {
+        let response: graphql_client::Response<substance_query::ResponseData> =
+            serde_json::from_slice(json)?;
+
+        if let Some(e) = response.errors.filter(|e| !e.is_empty()) {
+            return Err(PwikiError::GraphQl(e));
+        }
+
+        let metadata = response
+            .extensions
+            .and_then(|mut i| i.remove("snapshot"))
+            .map(serde_json::from_value::<SnapshotMetadata>)
+            .transpose()?;
+
+        if let Some(m) = metadata.as_ref() {
+            if m.schema_version > SNAPSHOT_SCHEMA_VERSION {
+                return Err(PwikiError::UnsupportedSnapshot(m.schema_version));
+            }
+        }
+
+        let substances = response
+            .data
+            .and_then(|i| i.substances)
+            .ok_or(PwikiError::MissingData("substances"))?
+            .into_iter()
+            .flatten()
+            .map(|i| i.into_substance(mode))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            metadata,
+            ..Self::new(substances)
+        })
+    }
+
+    /// Where and when the snapshot was taken, if it was written by
+    /// [`Snapshot`](crate::snapshot::Snapshot).
+    pub fn metadata(&self) -> Option<&SnapshotMetadata> {
+        self.metadata.as_ref()
+    }
+
+    pub fn substances(&self) -> &[Substance] {
+        &self.substances
+    }
+
+    pub fn len(&self) -> usize {
+        self.substances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.substances.is_empty()
+    }
+
+    /// Looks up a substance by its name or one of its common names, ignoring
+    /// case and punctuation like [`PwikiClient::resolve`], e.g. "lsd",
+    /// "Acid" or "lsd 25".
+    ///
+    /// [`PwikiClient::resolve`]: crate::client::PwikiClient::resolve
+    pub fn get(&self, name: &str) -> Option<&Substance> {
+        self.names
+            .get(&name_key(name))
+            .map(|&i| &self.substances[i])
+    }
+
+    /// Substances with `class` as either a chemical or psychoactive class.
+    pub fn by_class<'a>(&'a self, class: &'a str) -> impl Iterator<Item = &'a Substance> + 'a {
+        self.substances
+            .iter()
+            .filter(move |i| i.class.contains(class))
+    }
+
+    /// Substances matching every class set in `filter`, ignoring case.
+    pub fn matching<'a>(
+        &'a self,
+        filter: &'a ClassFilter,
+    ) -> impl Iterator<Item = &'a Substance> + 'a {
+        let has = |classes: &[String], class: &Option<String>| {
+            class
+                .as_ref()
+                .is_none_or(|c| classes.iter().any(|i| i.eq_ignore_ascii_case(c)))
+        };
+
+        self.substances.iter().filter(move |i| {
+            has(&i.class.chemical, &filter.chemical)
+                && has(&i.class.psychoactive, &filter.psychoactive)
+        })
+    }
+}
+
+impl From<Vec<Substance>> for SubstanceDatabase {
+    fn from(substances: Vec<Substance>) -> Self {
+        Self::new(substances)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::LSD_FIXTURE;
+
+    #[test]
+    fn test_lookups() {
+        let db = SubstanceDatabase::load(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/lsd.json"
+        ))
+        .unwrap();
+
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.get("lsd").unwrap().name, "LSD");
+        assert_eq!(db.get("ACID").unwrap().name, "LSD");
+        assert_eq!(db.get("lsd 25").unwrap().name, "LSD");
+        assert_eq!(db.get("LSD25").unwrap().name, "LSD");
+        assert!(db.get("-").is_none());
+
+        let two_cb = SubstanceDatabase::new(vec![Substance {
+            name: "2C-B".to_string(),
+            ..Default::default()
+        }]);
+        assert_eq!(two_cb.get("2cb").unwrap().name, "2C-B");
+        assert_eq!(two_cb.get("2C B").unwrap().name, "2C-B");
+        assert!(db.get("MDMA").is_none());
+
+        assert_eq!(db.by_class("Psychedelics").count(), 1);
+        assert_eq!(db.by_class("Dissociatives").count(), 0);
+        assert_eq!(
+            db.matching(&ClassFilter::chemical("lysergamides").and_psychoactive("psychedelics"))
+                .count(),
+            1
+        );
+        assert_eq!(
+            db.matching(&ClassFilter::chemical("psychedelics")).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_invalid_snapshot() {
+        let err =
+            SubstanceDatabase::from_json(b"{\"data\":{}}", ConversionMode::Strict).unwrap_err();
+        assert!(matches!(err, PwikiError::MissingData("substances")));
+
+        let err =
+            SubstanceDatabase::from_json(&LSD_FIXTURE.as_bytes()[..100], ConversionMode::Strict)
+                .unwrap_err();
+        assert!(matches!(err, PwikiError::Decode(_)));
+    }
+}
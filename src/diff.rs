@@ -0,0 +1,379 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::database::SubstanceDatabase;
+use crate::interaction::Interaction;
+use crate::structure::{
+    DoseMetadata, DoseRange, DoseTimeRange, DoseUnits, Duration, ROAs, Substance,
+};
+
+/// Everything that changed between two catalog snapshots.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CatalogDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Substances present in both catalogs with at least one change.
+    pub changed: Vec<SubstanceDiff>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SubstanceDiff {
+    pub name: String,
+    pub routes_added: Vec<ROAs>,
+    pub routes_removed: Vec<ROAs>,
+    pub doses: Vec<DoseChange>,
+    pub durations: Vec<DurationChange>,
+    pub interactions_added: Vec<Interaction>,
+    pub interactions_removed: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DoseLevel {
+    Threshold,
+    Light,
+    Common,
+    Strong,
+    Heavy,
+}
+
+/// A dose amount or range, `threshold` and `heavy` being single amounts
+/// represented as `x..x`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoseValue {
+    pub range: DoseRange,
+    pub units: DoseUnits,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DoseChange {
+    pub route: ROAs,
+    pub level: DoseLevel,
+    pub old: Option<DoseValue>,
+    pub new: Option<DoseValue>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DurationChange {
+    pub route: ROAs,
+    /// The duration phase, e.g. `"onset"`.
+    pub phase: &'static str,
+    pub old: Option<DoseTimeRange>,
+    pub new: Option<DoseTimeRange>,
+}
+
+impl PartialEq for DurationChange {
+    fn eq(&self, other: &Self) -> bool {
+        self.route == other.route
+            && self.phase == other.phase
+            && same_time_range(self.old.as_ref(), other.old.as_ref())
+            && same_time_range(self.new.as_ref(), other.new.as_ref())
+    }
+}
+
+impl CatalogDiff {
+    /// Compares two catalogs, matching substances by name ignoring case.
+    pub fn between(old: &SubstanceDatabase, new: &SubstanceDatabase) -> Self {
+        let old_by_name = by_name(old);
+        let new_by_name = by_name(new);
+        let mut diff = CatalogDiff::default();
+
+        for (key, substance) in &new_by_name {
+            match old_by_name.get(key) {
+                Some(previous) => {
+                    let changes = SubstanceDiff::between(previous, substance);
+                    if !changes.is_empty() {
+                        diff.changed.push(changes);
+                    }
+                }
+                None => diff.added.push(substance.name.clone()),
+            }
+        }
+
+        for (key, substance) in &old_by_name {
+            if !new_by_name.contains_key(key) {
+                diff.removed.push(substance.name.clone());
+            }
+        }
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// A changelog suitable for review, one section per changed substance.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::from("# Catalog changes\n");
+
+        if self.is_empty() {
+            out.push_str("\nNo changes.\n");
+            return out;
+        }
+
+        for (title, names) in [("Added", &self.added), ("Removed", &self.removed)] {
+            if !names.is_empty() {
+                let _ = writeln!(out, "\n## {title} substances\n");
+                for name in names {
+                    let _ = writeln!(out, "- {name}");
+                }
+            }
+        }
+
+        for substance in &self.changed {
+            let _ = writeln!(out, "\n## {}\n", substance.name);
+
+            for route in &substance.routes_added {
+                let _ = writeln!(out, "- added route {route:?}");
+            }
+            for route in &substance.routes_removed {
+                let _ = writeln!(out, "- removed route {route:?}");
+            }
+            for dose in &substance.doses {
+                let _ = writeln!(
+                    out,
+                    "- {:?} {:?} dose: {} → {}",
+                    dose.route,
+                    dose.level,
+                    fmt_dose(dose.old.as_ref()),
+                    fmt_dose(dose.new.as_ref())
+                );
+            }
+            for duration in &substance.durations {
+                let _ = writeln!(
+                    out,
+                    "- {:?} {}: {} → {}",
+                    duration.route,
+                    duration.phase,
+                    fmt_time_range(duration.old.as_ref()),
+                    fmt_time_range(duration.new.as_ref())
+                );
+            }
+            for interaction in &substance.interactions_added {
+                let _ = writeln!(
+                    out,
+                    "- added {} interaction with {}",
+                    interaction.severity, interaction.target
+                );
+            }
+            for interaction in &substance.interactions_removed {
+                let _ = writeln!(
+                    out,
+                    "- removed {} interaction with {}",
+                    interaction.severity, interaction.target
+                );
+            }
+        }
+
+        out
+    }
+}
+
+impl SubstanceDiff {
+    fn between(old: &Substance, new: &Substance) -> Self {
+        let mut diff = SubstanceDiff {
+            name: new.name.clone(),
+            ..Default::default()
+        };
+
+        for route in &new.routes_of_administration {
+            match old.route_of_administration(route.ty) {
+                Some(previous) => {
+                    diff.doses.extend(dose_changes(
+                        route.ty,
+                        &previous.dose_metadata,
+                        &route.dose_metadata,
+                    ));
+                    diff.durations.extend(duration_changes(
+                        route.ty,
+                        &previous.duration,
+                        &route.duration,
+                    ));
+                }
+                None => diff.routes_added.push(route.ty),
+            }
+        }
+
+        for route in &old.routes_of_administration {
+            if new.route_of_administration(route.ty).is_none() {
+                diff.routes_removed.push(route.ty);
+            }
+        }
+
+        diff.interactions_added = new
+            .interactions
+            .iter()
+            .filter(|i| !old.interactions.contains(i))
+            .cloned()
+            .collect();
+        diff.interactions_removed = old
+            .interactions
+            .iter()
+            .filter(|i| !new.interactions.contains(i))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes_added.is_empty()
+            && self.routes_removed.is_empty()
+            && self.doses.is_empty()
+            && self.durations.is_empty()
+            && self.interactions_added.is_empty()
+            && self.interactions_removed.is_empty()
+    }
+}
+
+fn by_name(db: &SubstanceDatabase) -> BTreeMap<String, &Substance> {
+    db.substances()
+        .iter()
+        .map(|i| (i.name.to_lowercase(), i))
+        .collect()
+}
+
+fn dose_changes(route: ROAs, old: &DoseMetadata, new: &DoseMetadata) -> Vec<DoseChange> {
+    let levels = |dose: &DoseMetadata| {
+        let single = |amount: Option<f64>| amount.map(|i| i..i);
+        [
+            (DoseLevel::Threshold, single(dose.threshold)),
+            (DoseLevel::Light, dose.light.clone()),
+            (DoseLevel::Common, dose.common.clone()),
+            (DoseLevel::Strong, dose.strong.clone()),
+            (DoseLevel::Heavy, single(dose.heavy)),
+        ]
+        .map(|(level, range)| {
+            let value = range.map(|range| DoseValue {
+                range,
+                units: dose.units,
+            });
+            (level, value)
+        })
+    };
+
+    levels(old)
+        .into_iter()
+        .zip(levels(new))
+        .filter(|((_, old), (_, new))| old != new)
+        .map(|((level, old), (_, new))| DoseChange {
+            route,
+            level,
+            old,
+            new,
+        })
+        .collect()
+}
+
+fn duration_changes(route: ROAs, old: &Duration, new: &Duration) -> Vec<DurationChange> {
+    let phases = |d: &Duration| {
+        [
+            ("onset", d.onset.clone()),
+            ("comeup", d.comeup.clone()),
+            ("peak", d.peak.clone()),
+            ("offset", d.offset.clone()),
+            ("afterglow", d.afterglow.clone()),
+            ("duration", d.duration.clone()),
+            ("total", d.total.clone()),
+        ]
+    };
+
+    phases(old)
+        .into_iter()
+        .zip(phases(new))
+        .filter(|((_, old), (_, new))| !same_time_range(old.as_ref(), new.as_ref()))
+        .map(|((phase, old), (_, new))| DurationChange {
+            route,
+            phase,
+            old,
+            new,
+        })
+        .collect()
+}
+
+fn same_time_range(lhs: Option<&DoseTimeRange>, rhs: Option<&DoseTimeRange>) -> bool {
+    match (lhs, rhs) {
+        (Some(l), Some(r)) => l.start == r.start && l.end == r.end && l.units == r.units,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn fmt_dose(dose: Option<&DoseValue>) -> String {
+    match dose {
+        Some(DoseValue { range, units }) if range.start == range.end => {
+            format!("{} {units}", range.start)
+        }
+        Some(DoseValue { range, units }) => format!("{}-{} {units}", range.start, range.end),
+        None => "none".to_string(),
+    }
+}
+
+fn fmt_time_range(range: Option<&DoseTimeRange>) -> String {
+    match range {
+        Some(r) => format!("{}-{} {:?}", r.start, r.end, r.units).to_lowercase(),
+        None => "none".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::interaction::Severity;
+    use crate::query::ConversionMode;
+    use crate::test_util::LSD_FIXTURE;
+
+    #[test]
+    fn test_catalog_diff() {
+        let old =
+            SubstanceDatabase::from_json(LSD_FIXTURE.as_bytes(), ConversionMode::Strict).unwrap();
+
+        let mut json: serde_json::Value = serde_json::from_str(LSD_FIXTURE).unwrap();
+        let lsd = &mut json["data"]["substances"][0];
+        lsd["roas"][1]["dose"]["common"]["min"] = 60.into();
+        lsd["roas"][1]["duration"]["onset"]["max"] = 45.into();
+        lsd["roas"].as_array_mut().unwrap().remove(0);
+        lsd["dangerousInteractions"] = serde_json::json!([{ "name": "Tramadol" }]);
+        json["data"]["substances"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!({ "name": "DMT" }));
+        let new = SubstanceDatabase::from_json(json.to_string().as_bytes(), ConversionMode::Strict)
+            .unwrap();
+
+        let diff = CatalogDiff::between(&old, &new);
+        assert_eq!(diff.added, ["DMT"]);
+        assert!(diff.removed.is_empty());
+
+        let lsd = &diff.changed[0];
+        assert_eq!(lsd.routes_removed, [ROAs::Oral]);
+        assert_eq!(
+            lsd.doses,
+            [DoseChange {
+                route: ROAs::Sublingual,
+                level: DoseLevel::Common,
+                old: Some(DoseValue {
+                    range: 75.0..150.0,
+                    units: DoseUnits::Ug,
+                }),
+                new: Some(DoseValue {
+                    range: 60.0..150.0,
+                    units: DoseUnits::Ug,
+                }),
+            }]
+        );
+        assert_eq!(lsd.durations[0].phase, "onset");
+        assert_eq!(
+            lsd.interactions_added,
+            [Interaction::new("Tramadol", Severity::Dangerous)]
+        );
+
+        let markdown = diff.to_markdown();
+        assert!(markdown.contains("## Added substances\n\n- DMT\n"));
+        assert!(markdown.contains("- Sublingual Common dose: 75-150 µg → 60-150 µg\n"));
+        assert!(markdown.contains("- Sublingual onset: 15-30 minutes → 15-45 minutes\n"));
+        assert!(markdown.contains("- added dangerous interaction with Tramadol\n"));
+
+        assert!(CatalogDiff::between(&new, &new).is_empty());
+    }
+}
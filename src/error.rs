@@ -1,29 +1,164 @@
 use std::fmt::Display;
+use std::time::Duration;
+
+use reqwest::StatusCode;
+
+use crate::structure::ROAs;
+
+pub type Result<T, E = PwikiError> = std::result::Result<T, E>;
 
 #[derive(Debug)]
-pub struct ApiError {
-    messages: Vec<String>,
-    err_count: usize,
+pub enum PwikiError {
+    /// The configured endpoint is not a valid URL.
+    InvalidEndpoint { endpoint: String, reason: String },
+    /// A builder option was given a value the client cannot work with, e.g.
+    /// a non-positive request rate.
+    InvalidConfig {
+        option: &'static str,
+        reason: String,
+    },
+    /// The request never produced a response: connection, TLS or timeout
+    /// failures, or the HTTP client could not be built.
+    Transport(reqwest::Error),
+    /// The endpoint answered with a non-success status code.
+    Status {
+        status: StatusCode,
+        body: String,
+        /// How long the endpoint asked clients to wait before retrying.
+        retry_after: Option<Duration>,
+    },
+    /// The request did not succeed within the deadline of the client's
+    /// [`RetryPolicy`](crate::RetryPolicy).
+    DeadlineExceeded { attempts: u32 },
+    /// The response body was not a valid GraphQL response.
+    Decode(serde_json::Error),
+    /// The endpoint reported one or more GraphQL errors.
+    GraphQl(Vec<graphql_client::Error>),
+    /// The response was well formed but did not contain the requested data.
+    MissingData(&'static str),
+    /// The API knows no substance by `name`.
+    NotFound { name: String },
+    /// No substance is exactly named `name`, neither by its name nor by one
+    /// of its common names, but the API matched others.
+    Ambiguous {
+        name: String,
+        /// The substances the API matched instead.
+        candidates: Vec<String>,
+    },
+    /// The returned data could not be turned into the public types.
+    Conversion(ConversionError),
+    /// Reading or writing local data failed.
+    Io(std::io::Error),
+    /// A snapshot was written by a newer version of this crate.
+    UnsupportedSnapshot(u32),
 }
 
-impl ApiError {
-    pub fn new(messages: Vec<String>) -> Self {
-        let len = messages.len();
-        ApiError {
-            messages,
-            err_count: len,
+impl PwikiError {
+    /// Whether retrying the same request later may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            PwikiError::Transport(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+            PwikiError::Status { status, .. } => {
+                status.is_server_error() || *status == StatusCode::TOO_MANY_REQUESTS
+            }
+            _ => false,
         }
     }
 }
 
-impl Display for ApiError {
+impl Display for PwikiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PwikiError::InvalidEndpoint { endpoint, reason } => {
+                write!(f, "invalid endpoint {endpoint:?}: {reason}")
+            }
+            PwikiError::InvalidConfig { option, reason } => write!(f, "invalid {option}: {reason}"),
+            PwikiError::Transport(e) => write!(f, "transport error: {e}"),
+            PwikiError::Status { status, .. } => write!(f, "unexpected status: {status}"),
+            PwikiError::DeadlineExceeded { attempts } => {
+                write!(f, "deadline exceeded after {attempts} attempt(s)")
+            }
+            PwikiError::Decode(e) => write!(f, "invalid response body: {e}"),
+            PwikiError::GraphQl(errors) => {
+                let msg_fmt = errors
+                    .iter()
+                    .fold(String::new(), |acc, i| acc + "\n" + &i.to_string());
+                write!(f, "Error count: {}{}", errors.len(), msg_fmt)
+            }
+            PwikiError::MissingData(what) => write!(f, "missing {what} in response"),
+            PwikiError::NotFound { name } => write!(f, "no substance named {name:?}"),
+            PwikiError::Ambiguous { name, candidates } => write!(
+                f,
+                "no substance named {name:?}, did you mean one of: {}",
+                candidates.join(", ")
+            ),
+            PwikiError::Conversion(e) => write!(f, "invalid data: {e}"),
+            PwikiError::Io(e) => write!(f, "i/o error: {e}"),
+            PwikiError::UnsupportedSnapshot(version) => {
+                write!(f, "unsupported snapshot schema version {version}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PwikiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PwikiError::Transport(e) => Some(e),
+            PwikiError::Decode(e) => Some(e),
+            PwikiError::Conversion(e) => Some(e),
+            PwikiError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for PwikiError {
+    fn from(e: reqwest::Error) -> Self {
+        PwikiError::Transport(e)
+    }
+}
+
+impl From<serde_json::Error> for PwikiError {
+    fn from(e: serde_json::Error) -> Self {
+        PwikiError::Decode(e)
+    }
+}
+
+impl From<std::io::Error> for PwikiError {
+    fn from(e: std::io::Error) -> Self {
+        PwikiError::Io(e)
+    }
+}
+
+impl From<ConversionError> for PwikiError {
+    fn from(e: ConversionError) -> Self {
+        PwikiError::Conversion(e)
+    }
+}
+
+/// A value in a duration phase that could not be interpreted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConversionError {
+    pub substance: String,
+    pub route: ROAs,
+    /// The duration phase, e.g. `"onset"`.
+    pub phase: String,
+    /// The field within the phase, e.g. `"units"`.
+    pub field: String,
+    /// The offending value as returned by the API.
+    pub value: String,
+}
+
+impl Display for ConversionError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let msg_fmt = self
-            .messages
-            .iter()
-            .fold(String::new(), |acc, i| acc + i + "\n");
-        write!(f, "Error count: {}\n{}", self.err_count, msg_fmt)
+        write!(
+            f,
+            "{} ({:?}): invalid {}.{} {:?}",
+            self.substance, self.route, self.phase, self.field, self.value
+        )
     }
 }
 
-impl std::error::Error for ApiError {}
+impl std::error::Error for ConversionError {}
@@ -0,0 +1,841 @@
+use std::cmp::{Ordering, Reverse};
+use std::fmt::{self, Write};
+use std::ops::Range;
+
+use chrono::{DateTime, Utc};
+
+use crate::database::SubstanceDatabase;
+use crate::structure::{name_key, Ingestion, Substance, SubstanceClass};
+
+/// Abbreviations used in interaction entries for classes that are spelled
+/// out elsewhere, as class keys.
+const CLASS_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("maoi", "monoamineoxidaseinhibitor"),
+    ("ssri", "selectiveserotoninreuptakeinhibitor"),
+    ("snri", "serotoninnorepinephrinereuptakeinhibitor"),
+];
+
+/// Characters a single wildcard of a family like "2C-x" stands for. It must
+/// start at an uppercase letter or a digit, like the "B" of 2C-B or the "DiP"
+/// of 5-MeO-DiPT, so it never swallows the tail of an ordinary word.
+const WILDCARD_LEN: std::ops::RangeInclusive<usize> = 1..=3;
+
+/// How risky a combination is, as classified by the wiki.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    Uncertain,
+    Unsafe,
+    Dangerous,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Uncertain => "uncertain",
+            Severity::Unsafe => "unsafe",
+            Severity::Dangerous => "dangerous",
+        })
+    }
+}
+
+/// What an [`Interaction`] entry names, as far as can be told from the
+/// entry alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum TargetKind {
+    Substance,
+    /// A chemical or psychoactive class, e.g. "Stimulants" or "MAOIs".
+    Class,
+    /// A wildcard family, e.g. "2C-x" or "DOx".
+    Family,
+}
+
+impl TargetKind {
+    pub fn of(target: &str) -> Self {
+        if family(target).is_some() {
+            return TargetKind::Family;
+        }
+
+        // classes are plural, unlike e.g. "Cannabis", "Nitrous" or "Grass"
+        let mut end = target.chars().rev();
+        match (end.next(), end.next()) {
+            (Some('s'), Some(i)) if !matches!(i, 'i' | 's' | 'u') => TargetKind::Class,
+            _ if CLASS_ABBREVIATIONS
+                .iter()
+                .any(|(i, _)| *i == name_key(target)) =>
+            {
+                TargetKind::Class
+            }
+            _ => TargetKind::Substance,
+        }
+    }
+}
+
+/// An entry of a substance's interaction lists on the wiki.
+///
+/// Ordered by severity first, so the most severe of several interactions
+/// is their maximum.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Interaction {
+    /// The substance, class or family as written on the wiki.
+    pub target: String,
+    pub severity: Severity,
+    pub target_kind: TargetKind,
+}
+
+impl Interaction {
+    pub fn new(target: impl Into<String>, severity: Severity) -> Self {
+        let target = target.into();
+
+        Self {
+            target_kind: TargetKind::of(&target),
+            target,
+            severity,
+        }
+    }
+
+    /// Whether this entry applies to `substance`, by its name, one of its
+    /// classes or as a wildcard family, e.g. "2C-x" for 2C-B or "DOx" for
+    /// DOM.
+    ///
+    /// Families are only matched against the substance's own name, as common
+    /// names are often slang, e.g. "Dope" for cannabis. Names and classes are
+    /// both tried whatever the [`target_kind`](Interaction::target_kind), as it is only a guess.
+    pub fn applies_to(&self, substance: &Substance) -> bool {
+        if self.target_kind == TargetKind::Family {
+            return family(&self.target)
+                .is_some_and(|pattern| matches_family(&pattern, &family_key(&substance.name)));
+        }
+
+        substance.is_named(&self.target) || names_class(&self.target, &substance.class)
+    }
+}
+
+impl PartialOrd for Interaction {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Interaction {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.severity, &self.target, self.target_kind).cmp(&(
+            other.severity,
+            &other.target,
+            other.target_kind,
+        ))
+    }
+}
+
+/// Two substances that interact, whichever of them lists the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InteractionPair {
+    pub first: String,
+    pub second: String,
+    /// The most severe of the two sides.
+    pub severity: Severity,
+    /// The entry of `first` that applies to `second`, if any.
+    pub first_to_second: Option<Interaction>,
+    /// The entry of `second` that applies to `first`, if any.
+    pub second_to_first: Option<Interaction>,
+}
+
+impl InteractionPair {
+    /// Whether the two substances disagree on the severity, or only one of
+    /// them lists the other.
+    pub fn is_asymmetric(&self) -> bool {
+        self.first_to_second.as_ref().map(|i| i.severity)
+            != self.second_to_first.as_ref().map(|i| i.severity)
+    }
+
+    /// The substance whose interactions understate the pair, i.e. the page
+    /// to correct. `None` if both sides agree.
+    pub fn understated_by(&self) -> Option<&str> {
+        let first = self.first_to_second.as_ref().map(|i| i.severity);
+        let second = self.second_to_first.as_ref().map(|i| i.severity);
+
+        match first.cmp(&second) {
+            Ordering::Less => Some(&self.first),
+            Ordering::Greater => Some(&self.second),
+            Ordering::Equal => None,
+        }
+    }
+}
+
+/// Every interacting pair among a set of substances, most severe first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InteractionReport {
+    pub pairs: Vec<InteractionPair>,
+}
+
+impl InteractionReport {
+    /// Checks every pair of `substances`. Substances with the same name are
+    /// considered once.
+    pub fn for_substances<'a>(substances: impl IntoIterator<Item = &'a Substance>) -> Self {
+        let mut unique: Vec<&Substance> = Vec::new();
+        for substance in substances {
+            if !unique.iter().any(|i| i.name == substance.name) {
+                unique.push(substance);
+            }
+        }
+
+        let mut pairs = Vec::new();
+        for (i, first) in unique.iter().enumerate() {
+            pairs.extend(unique[i + 1..].iter().filter_map(|i| pair(first, i)));
+        }
+
+        // stable, so pairs of equal severity keep the order they were given in
+        pairs.sort_by_key(|i| Reverse(i.severity));
+        Self { pairs }
+    }
+
+    /// Checks the substances of every pair of `ingestions`, e.g. everything
+    /// taken in the last day.
+    pub fn for_ingestions<'a>(ingestions: impl IntoIterator<Item = &'a Ingestion>) -> Self {
+        Self::for_substances(ingestions.into_iter().map(|i| &i.substance))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    pub fn most_severe(&self) -> Option<Severity> {
+        self.pairs.first().map(|i| i.severity)
+    }
+
+    /// Pairs with `severity` or worse.
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &InteractionPair> {
+        self.pairs.iter().filter(move |i| i.severity >= severity)
+    }
+
+    /// Pairs the two substances disagree on, most severe first.
+    pub fn asymmetric(&self) -> impl Iterator<Item = &InteractionPair> {
+        self.pairs.iter().filter(|i| i.is_asymmetric())
+    }
+
+    /// Copies of `substances` in which both substances of every pair list
+    /// each other at the pair's severity, the most severe of both sides.
+    ///
+    /// Only substances among `substances` are amended, so a report built
+    /// from the same set is symmetric.
+    pub fn reconciled<'a>(
+        &self,
+        substances: impl IntoIterator<Item = &'a Substance>,
+    ) -> Vec<Substance> {
+        let mut substances: Vec<Substance> = substances.into_iter().cloned().collect();
+
+        for pair in self.asymmetric() {
+            let (understated, other) = match pair.understated_by() {
+                Some(name) if name == pair.first => (&pair.first, &pair.second),
+                Some(_) => (&pair.second, &pair.first),
+                None => continue,
+            };
+
+            for substance in substances.iter_mut().filter(|i| &i.name == understated) {
+                substance.interactions.push(Interaction {
+                    target: other.clone(),
+                    severity: pair.severity,
+                    target_kind: TargetKind::Substance,
+                });
+            }
+        }
+
+        substances
+    }
+
+    /// The asymmetric pairs as a markdown list, naming the page to correct
+    /// first, e.g. "- LSD lists Lithium as unsafe, but Lithium lists LSD as
+    /// dangerous".
+    pub fn asymmetry_markdown(&self) -> String {
+        let mut out = String::new();
+
+        for pair in self.asymmetric() {
+            let (understated, other, from, to) = if pair.understated_by() == Some(&pair.first) {
+                (
+                    &pair.first,
+                    &pair.second,
+                    &pair.first_to_second,
+                    &pair.second_to_first,
+                )
+            } else {
+                (
+                    &pair.second,
+                    &pair.first,
+                    &pair.second_to_first,
+                    &pair.first_to_second,
+                )
+            };
+
+            let _ = writeln!(
+                out,
+                "- {} {}, but {} {}",
+                understated,
+                fmt_side(other, from.as_ref()),
+                other,
+                fmt_side(understated, to.as_ref()),
+            );
+        }
+
+        out
+    }
+}
+
+/// How `first` and `second` interact, if either lists the other.
+fn pair(first: &Substance, second: &Substance) -> Option<InteractionPair> {
+    let first_to_second = first.interaction_with(second);
+    let second_to_first = second.interaction_with(first);
+
+    Some(InteractionPair {
+        first: first.name.clone(),
+        second: second.name.clone(),
+        severity: first_to_second.max(second_to_first)?.severity,
+        first_to_second: first_to_second.cloned(),
+        second_to_first: second_to_first.cloned(),
+    })
+}
+
+impl From<&SubstanceDatabase> for InteractionReport {
+    fn from(db: &SubstanceDatabase) -> Self {
+        Self::for_substances(db.substances())
+    }
+}
+
+/// Two interacting ingestions whose effects overlap.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OverlapWarning {
+    /// Indices of the two ingestions in those the report was built from.
+    pub ingestions: (usize, usize),
+    pub pair: InteractionPair,
+    /// When both substances were active at once.
+    pub overlap: Range<DateTime<Utc>>,
+}
+
+/// Interacting ingestions that were active at the same time, as opposed to
+/// an [`InteractionReport`] of everything ever taken together.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OverlapReport {
+    /// Most severe first, then by the start of the overlap.
+    pub warnings: Vec<OverlapWarning>,
+    /// Indices of ingestions without duration data for their route, which
+    /// could not be checked.
+    pub unknown: Vec<usize>,
+}
+
+impl OverlapReport {
+    /// Checks every pair of `ingestions` of different substances, using
+    /// their [active windows](Ingestion::active_window).
+    pub fn for_ingestions(ingestions: &[Ingestion], include_afterglow: bool) -> Self {
+        let windows: Vec<_> = ingestions
+            .iter()
+            .map(|i| i.active_window(include_afterglow))
+            .collect();
+
+        let mut report = Self::default();
+        for (i, first) in ingestions.iter().enumerate() {
+            let Some(first_window) = &windows[i] else {
+                report.unknown.push(i);
+                continue;
+            };
+
+            for (j, second) in ingestions.iter().enumerate().skip(i + 1) {
+                let Some(second_window) = &windows[j] else {
+                    continue;
+                };
+                if first.substance.name == second.substance.name {
+                    continue;
+                }
+
+                let start = first_window.start.max(second_window.start);
+                let end = first_window.end.min(second_window.end);
+                if start >= end {
+                    continue;
+                }
+
+                if let Some(pair) = pair(&first.substance, &second.substance) {
+                    report.warnings.push(OverlapWarning {
+                        ingestions: (i, j),
+                        pair,
+                        overlap: start..end,
+                    });
+                }
+            }
+        }
+
+        report
+            .warnings
+            .sort_by_key(|i| (Reverse(i.pair.severity), i.overlap.start));
+        report
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Warnings whose overlap includes `time`, e.g. now.
+    pub fn active_at(&self, time: DateTime<Utc>) -> impl Iterator<Item = &OverlapWarning> {
+        self.warnings
+            .iter()
+            .filter(move |i| i.overlap.contains(&time))
+    }
+}
+
+/// How one side of a pair lists `target`, naming the entry when it is a
+/// class, family or other name, e.g. "lists LSD as unsafe (via
+/// Psychedelics)".
+fn fmt_side(target: &str, interaction: Option<&Interaction>) -> String {
+    match interaction {
+        Some(i) if name_key(&i.target) == name_key(target) => {
+            format!("lists {target} as {}", i.severity)
+        }
+        Some(i) => format!("lists {target} as {} (via {})", i.severity, i.target),
+        None => format!("does not list {target}"),
+    }
+}
+
+/// `name` as a key that ignores plurals, a leading "substituted" and
+/// known abbreviations, so that "MAOIs" names "Monoamine oxidase
+/// inhibitor".
+fn class_key(name: &str) -> String {
+    let key = name_key(name);
+    let key = key.strip_prefix("substituted").unwrap_or(&key);
+    let key = key.strip_suffix('s').unwrap_or(key);
+
+    CLASS_ABBREVIATIONS
+        .iter()
+        .find(|(abbreviation, _)| *abbreviation == key)
+        .map_or(key, |(_, class)| class)
+        .to_string()
+}
+
+fn names_class(entry: &str, class: &SubstanceClass) -> bool {
+    let entry = class_key(entry);
+    if entry.is_empty() {
+        return false;
+    }
+
+    class
+        .chemical
+        .iter()
+        .chain(&class.psychoactive)
+        .any(|i| class_key(i) == entry)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    Wildcard,
+}
+
+/// Parses a family like "2C-x", "25x-NBOMe" or "5-MeO-xxT", where a run of
+/// lowercase x that is not part of a word stands for the varying part.
+/// `None` for plain names, e.g. "Dextromethorphan" or "DXM".
+fn family(entry: &str) -> Option<Vec<Token>> {
+    let chars: Vec<char> = entry.chars().collect();
+    let mut pattern = Vec::new();
+
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] == 'x' {
+            let end = i + chars[i..].iter().take_while(|&&c| c == 'x').count();
+            let before = i.checked_sub(1).map(|j| chars[j]);
+            let after = chars.get(end).copied();
+
+            if !before.is_some_and(char::is_lowercase) && !after.is_some_and(char::is_lowercase) {
+                pattern.push(Token::Wildcard);
+                i = end;
+                continue;
+            }
+        }
+
+        if chars[i].is_alphanumeric() {
+            pattern.extend(chars[i].to_lowercase().map(Token::Literal));
+        }
+        i += 1;
+    }
+
+    let literals = pattern.iter().any(|i| matches!(i, Token::Literal(_)));
+    (literals && pattern.contains(&Token::Wildcard)).then_some(pattern)
+}
+
+/// A name as its [`name_key`] characters, each paired with whether a
+/// wildcard may start there.
+fn family_key(name: &str) -> Vec<(char, bool)> {
+    name.chars()
+        .filter(|i| i.is_alphanumeric())
+        .flat_map(|i| {
+            let starts = i.is_uppercase() || i.is_numeric();
+            i.to_lowercase().map(move |c| (c, starts))
+        })
+        .collect()
+}
+
+fn matches_family(pattern: &[Token], key: &[(char, bool)]) -> bool {
+    match pattern.split_first() {
+        None => key.is_empty(),
+        Some((Token::Literal(c), rest)) => {
+            key.first().is_some_and(|(i, _)| i == c) && matches_family(rest, &key[1..])
+        }
+        Some((Token::Wildcard, rest)) => {
+            key.first().is_some_and(|&(_, starts)| starts)
+                && WILDCARD_LEN
+                    .take_while(|&n| n <= key.len())
+                    .any(|n| matches_family(rest, &key[n..]))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::structure::{
+        DoseTimeRange, DoseUnits, Duration, ROAs, RouteOfAdministration, TimeUnits,
+    };
+
+    fn substance(
+        name: &str,
+        uncertain: &[&str],
+        r#unsafe: &[&str],
+        dangerous: &[&str],
+    ) -> Substance {
+        let interactions = |targets: &[&str], severity| {
+            targets
+                .iter()
+                .map(move |i| Interaction::new(*i, severity))
+                .collect::<Vec<_>>()
+        };
+
+        Substance {
+            name: name.to_string(),
+            interactions: [
+                interactions(uncertain, Severity::Uncertain),
+                interactions(r#unsafe, Severity::Unsafe),
+                interactions(dangerous, Severity::Dangerous),
+            ]
+            .concat(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_interaction() {
+        assert_eq!(TargetKind::of("Tramadol"), TargetKind::Substance);
+        assert_eq!(TargetKind::of("Cannabis"), TargetKind::Substance);
+        assert_eq!(TargetKind::of("Stimulants"), TargetKind::Class);
+        assert_eq!(TargetKind::of("MAOIs"), TargetKind::Class);
+        assert_eq!(TargetKind::of("SSRI"), TargetKind::Class);
+        assert_eq!(TargetKind::of("2C-T-x"), TargetKind::Family);
+
+        let lsd = substance("LSD", &["Cannabis", "Stimulants"], &["Lithium"], &[]);
+        let amphetamine = Substance {
+            class: SubstanceClass {
+                chemical: vec!["Amphetamines".to_string()],
+                psychoactive: vec!["Stimulants".to_string()],
+            },
+            ..substance("Amphetamine", &[], &[], &[])
+        };
+        assert_eq!(
+            lsd.interaction_with(&amphetamine),
+            Some(&Interaction {
+                target: "Stimulants".to_string(),
+                severity: Severity::Uncertain,
+                target_kind: TargetKind::Class,
+            })
+        );
+        assert_eq!(lsd.interaction_with(&lsd), None);
+        assert_eq!(lsd.interactions.iter().max().unwrap().target, "Lithium");
+
+        let targets: Vec<_> = lsd
+            .interactions_at_least(Severity::Unsafe)
+            .map(|i| i.target.as_str())
+            .collect();
+        assert_eq!(targets, ["Lithium"]);
+    }
+
+    #[test]
+    fn test_interaction_report() {
+        let lsd = substance("LSD", &["Cannabis", "Tramadol"], &["Lithium"], &[]);
+        let lithium = substance("Lithium", &[], &[], &["LSD"]);
+        let cannabis = substance("Cannabis", &[], &[], &[]);
+        let tramadol = substance("Tramadol", &["lsd"], &[], &[]);
+        let mdma = substance("MDMA", &[], &[], &[]);
+
+        let report =
+            InteractionReport::for_substances([&cannabis, &lsd, &mdma, &lithium, &tramadol]);
+        assert_eq!(
+            report.pairs[0],
+            InteractionPair {
+                first: "LSD".to_string(),
+                second: "Lithium".to_string(),
+                severity: Severity::Dangerous,
+                first_to_second: Some(Interaction::new("Lithium", Severity::Unsafe)),
+                second_to_first: Some(Interaction::new("LSD", Severity::Dangerous)),
+            }
+        );
+        let pairs: Vec<_> = report.pairs[1..]
+            .iter()
+            .map(|i| (i.first.as_str(), i.second.as_str(), i.severity))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("Cannabis", "LSD", Severity::Uncertain),
+                ("LSD", "Tramadol", Severity::Uncertain),
+            ]
+        );
+        assert_eq!(report.at_least(Severity::Unsafe).count(), 1);
+
+        let ingestion = |substance: &Substance| {
+            substance.new_ingestion(1.0, DoseUnits::Mg, Utc::now(), ROAs::Oral)
+        };
+        let report = InteractionReport::for_ingestions(&[
+            ingestion(&lsd),
+            ingestion(&lithium),
+            ingestion(&lsd),
+        ]);
+        assert_eq!(report.pairs.len(), 1);
+        assert_eq!(report.most_severe(), Some(Severity::Dangerous));
+        assert!(InteractionReport::for_ingestions(&[ingestion(&mdma)]).is_empty());
+    }
+
+    #[test]
+    fn test_applies_to() {
+        let applies_to = |target: &str, substance: &Substance| {
+            Interaction::new(target, Severity::Uncertain).applies_to(substance)
+        };
+        let two_cb = Substance {
+            common_names: vec!["Nexus".to_string()],
+            class: SubstanceClass {
+                chemical: vec!["Substituted phenethylamines".to_string()],
+                psychoactive: vec!["Psychedelics".to_string()],
+            },
+            ..substance("2C-B", &[], &[], &[])
+        };
+        let phenelzine = Substance {
+            class: SubstanceClass {
+                chemical: vec!["Hydrazines".to_string()],
+                psychoactive: vec!["Monoamine oxidase inhibitor".to_string()],
+            },
+            ..substance("Phenelzine", &[], &[], &["2C-x"])
+        };
+
+        assert!(applies_to("nexus", &two_cb));
+        assert!(applies_to("Psychedelic", &two_cb));
+        assert!(applies_to("Phenethylamines", &two_cb));
+        assert!(applies_to("2C-x", &two_cb));
+        assert!(!applies_to("DOx", &two_cb));
+        assert!(!applies_to("Stimulants", &two_cb));
+        assert!(applies_to("MAOIs", &phenelzine));
+
+        let dom = substance("DOM", &[], &[], &[]);
+        let nbome = substance("25I-NBOMe", &[], &[], &[]);
+        let dmt = substance("5-MeO-DMT", &[], &[], &[]);
+        assert!(applies_to("DOx", &dom));
+        assert!(applies_to("25x-NBOMe", &nbome));
+        assert!(applies_to("5-MeO-xxT", &dmt));
+        assert!(applies_to(
+            "5-MeO-xxT",
+            &substance("5-MeO-DiPT", &[], &[], &[])
+        ));
+        assert!(!applies_to("2C-x", &substance("2C-B-FLY", &[], &[], &[])));
+
+        let cannabis = Substance {
+            common_names: vec!["Dope".to_string(), "2C-Weed".to_string()],
+            ..substance("Cannabis", &[], &[], &[])
+        };
+        assert!(!applies_to("DOx", &cannabis));
+        assert!(!applies_to("2C-x", &cannabis));
+        assert!(!applies_to("DOx", &substance("Dope", &[], &[], &[])));
+        assert!(!applies_to("DOx", &substance("Dox", &[], &[], &[])));
+        assert_eq!(family("Dextromethorphan"), None);
+        assert_eq!(family("DXM"), None);
+        assert_eq!(family("Oxycodone"), None);
+
+        let report = InteractionReport::for_substances([&two_cb, &phenelzine]);
+        assert_eq!(report.most_severe(), Some(Severity::Dangerous));
+        assert_eq!(
+            report.pairs[0].second_to_first,
+            Some(Interaction::new("2C-x", Severity::Dangerous))
+        );
+
+        let two_cb = Substance {
+            interactions: vec![Interaction::new("MAOIs", Severity::Dangerous)],
+            ..two_cb
+        };
+        let moclobemide = Substance {
+            class: phenelzine.class.clone(),
+            ..substance("Moclobemide", &[], &[], &[])
+        };
+        let report = InteractionReport::for_substances([&two_cb, &moclobemide]);
+        assert_eq!(
+            report.pairs[0].first_to_second,
+            Some(Interaction::new("MAOIs", Severity::Dangerous))
+        );
+    }
+
+    #[test]
+    fn test_asymmetry() {
+        let lsd = Substance {
+            common_names: vec!["Acid".to_string()],
+            class: SubstanceClass {
+                chemical: vec!["Lysergamides".to_string()],
+                psychoactive: vec!["Psychedelics".to_string()],
+            },
+            ..substance("LSD", &["Cannabis"], &["Lithium"], &[])
+        };
+        let lithium = substance("Lithium", &[], &[], &["Psychedelics"]);
+        let cannabis = substance("Cannabis", &[], &[], &[]);
+        let tramadol = substance("Tramadol", &[], &["Acid"], &[]);
+        let substances = [lsd, lithium, cannabis, tramadol];
+
+        let report = InteractionReport::for_substances(&substances);
+        let understated: Vec<_> = report
+            .asymmetric()
+            .map(|i| i.understated_by().unwrap())
+            .collect();
+        assert_eq!(understated, ["LSD", "LSD", "Cannabis"]);
+
+        assert_eq!(
+            report.asymmetry_markdown(),
+            "- LSD lists Lithium as unsafe, but Lithium lists LSD as dangerous (via Psychedelics)\n\
+             - LSD does not list Tramadol, but Tramadol lists LSD as unsafe (via Acid)\n\
+             - Cannabis does not list LSD, but LSD lists Cannabis as uncertain\n"
+        );
+
+        let reconciled = report.reconciled(&substances);
+        let lsd = &reconciled[0];
+        assert_eq!(
+            lsd.interaction_with(&reconciled[1]).map(|i| i.severity),
+            Some(Severity::Dangerous)
+        );
+        assert_eq!(
+            reconciled[2].interaction_with(lsd).map(|i| i.severity),
+            Some(Severity::Uncertain)
+        );
+
+        let report = InteractionReport::for_substances(&reconciled);
+        assert_eq!(report.asymmetric().count(), 0);
+        assert!(report.asymmetry_markdown().is_empty());
+        assert_eq!(report.pairs.len(), 3);
+    }
+
+    #[test]
+    fn test_overlap_report() {
+        let hours = |start: f64, end: f64| {
+            Some(DoseTimeRange {
+                start,
+                end,
+                midpoint: (start + end) / 2.0,
+                units: TimeUnits::Hours,
+                ..Default::default()
+            })
+        };
+        let with_duration = |substance: Substance, duration: Duration| Substance {
+            routes_of_administration: vec![RouteOfAdministration {
+                ty: ROAs::Oral,
+                dose_metadata: Default::default(),
+                duration,
+                bioavailability: None,
+            }],
+            ..substance
+        };
+
+        let lsd = with_duration(
+            substance("LSD", &["Cannabis"], &[], &[]),
+            Duration {
+                onset: hours(0.5, 1.0),
+                comeup: hours(1.0, 1.0),
+                peak: hours(2.0, 2.0),
+                offset: hours(1.0, 2.0),
+                afterglow: hours(3.0, 6.0),
+                ..Default::default()
+            },
+        );
+        let lithium = with_duration(
+            substance("Lithium", &[], &[], &["LSD"]),
+            Duration {
+                total: hours(12.0, 24.0),
+                ..Default::default()
+            },
+        );
+        let cannabis = with_duration(
+            substance("Cannabis", &[], &[], &[]),
+            Duration {
+                total: hours(1.0, 2.0),
+                ..Default::default()
+            },
+        );
+        let tramadol = substance("Tramadol", &["LSD"], &[], &[]);
+
+        let taken = "2023-01-01T20:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let at = |h: i64| taken + chrono::Duration::hours(h);
+        let ingestions = [
+            lsd.new_ingestion(100.0, DoseUnits::Ug, at(0), ROAs::Oral),
+            lithium.new_ingestion(300.0, DoseUnits::Mg, at(3), ROAs::Oral),
+            cannabis.new_ingestion(10.0, DoseUnits::Mg, at(10), ROAs::Oral),
+            tramadol.new_ingestion(50.0, DoseUnits::Mg, at(0), ROAs::Oral),
+        ];
+        assert_eq!(
+            ingestions[0].active_window(false),
+            Some(taken + chrono::Duration::minutes(30)..at(6))
+        );
+
+        let report = OverlapReport::for_ingestions(&ingestions, false);
+        assert_eq!(report.warnings.len(), 1);
+        let warning = &report.warnings[0];
+        assert_eq!(warning.ingestions, (0, 1));
+        assert_eq!(warning.pair.severity, Severity::Dangerous);
+        assert_eq!(warning.overlap, at(3)..at(6));
+        assert_eq!(report.unknown, [3]);
+        assert_eq!(report.active_at(at(4)).count(), 1);
+        assert_eq!(report.active_at(at(7)).count(), 0);
+
+        let report = OverlapReport::for_ingestions(&ingestions, true);
+        let overlaps: Vec<_> = report
+            .warnings
+            .iter()
+            .map(|i| (i.ingestions, i.overlap.clone()))
+            .collect();
+        assert_eq!(
+            overlaps,
+            [((0, 1), at(3)..at(12)), ((0, 2), at(10)..at(12))]
+        );
+
+        assert!(OverlapReport::for_ingestions(&ingestions[2..], true).is_empty());
+
+        // data that converts fine but cannot be a window
+        let endless = with_duration(
+            substance("Lithium", &[], &[], &["LSD"]),
+            Duration {
+                total: hours(1.0, 1e12),
+                ..Default::default()
+            },
+        );
+        let early = with_duration(
+            substance("Lithium", &[], &[], &["LSD"]),
+            Duration {
+                onset: hours(-2.0, 1.0),
+                total: hours(4.0, 8.0),
+                ..Default::default()
+            },
+        );
+        for lithium in [endless, early] {
+            let ingestions = [
+                lsd.new_ingestion(100.0, DoseUnits::Ug, at(0), ROAs::Oral),
+                lithium.new_ingestion(300.0, DoseUnits::Mg, at(0), ROAs::Oral),
+            ];
+            assert_eq!(ingestions[1].active_window(true), None);
+
+            let report = OverlapReport::for_ingestions(&ingestions, true);
+            assert!(report.is_empty());
+            assert_eq!(report.unknown, [1]);
+        }
+    }
+}
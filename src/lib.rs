@@ -1,5 +1,31 @@
-pub mod query;
+pub mod cache;
+pub mod client;
+pub mod database;
+pub mod diff;
 pub mod error;
+pub mod interaction;
+pub mod limit;
+pub mod query;
+pub mod retry;
+pub mod search;
+pub mod snapshot;
 pub mod structure;
 
+#[cfg(test)]
+mod test_util;
+
+pub use cache::CacheOptions;
 pub use chrono;
+pub use client::{
+    BatchLookup, CatalogOptions, ClassFilter, Fetched, Page, PwikiClient, PwikiClientBuilder,
+    Staleness,
+};
+pub use database::SubstanceDatabase;
+pub use diff::CatalogDiff;
+pub use error::{PwikiError, Result};
+pub use interaction::{Interaction, InteractionReport, OverlapReport, Severity, TargetKind};
+pub use limit::{RateLimit, WaitMetrics};
+pub use query::ConversionMode;
+pub use retry::RetryPolicy;
+pub use search::SearchIndex;
+pub use snapshot::{Snapshot, SnapshotMetadata};
@@ -0,0 +1,211 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::{sleep, Instant};
+
+use crate::error::{PwikiError, Result};
+
+/// Limits on the requests sent by a client and all of its clones, see
+/// [`PwikiClientBuilder::rate_limit`](crate::client::PwikiClientBuilder::rate_limit).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimit {
+    /// Requests started per second on average, unlimited if unset. Must be
+    /// finite and positive.
+    pub requests_per_second: Option<f64>,
+    /// Requests that may start at once after a quiet period.
+    pub burst: u32,
+    /// Requests awaiting a response at once, unlimited if unset.
+    pub max_in_flight: Option<usize>,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self {
+            requests_per_second: None,
+            burst: 1,
+            max_in_flight: None,
+        }
+    }
+}
+
+/// How long requests waited for the [`RateLimit`] to let them through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WaitMetrics {
+    pub requests: u64,
+    /// Requests that could not start right away.
+    pub delayed: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl WaitMetrics {
+    pub fn average_wait(&self) -> Duration {
+        match self.requests {
+            0 => Duration::ZERO,
+            n => Duration::from_nanos((self.total_wait.as_nanos() / n as u128) as u64),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    bucket: Option<Mutex<TokenBucket>>,
+    in_flight: Option<Arc<Semaphore>>,
+    metrics: Mutex<WaitMetrics>,
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    per_second: f64,
+    capacity: f64,
+    /// Negative while requests are waiting for tokens that have not been
+    /// refilled yet.
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    /// Takes a token, returning how long to wait until it is available.
+    fn reserve(&mut self) -> Duration {
+        let now = Instant::now();
+        let refill = (now - self.refilled_at).as_secs_f64() * self.per_second;
+        self.tokens = (self.tokens + refill).min(self.capacity);
+        self.refilled_at = now;
+
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Result<Self> {
+        if let Some(per_second) = limit.requests_per_second {
+            if !(per_second.is_finite() && per_second > 0.0) {
+                return Err(PwikiError::InvalidConfig {
+                    option: "requests_per_second",
+                    reason: format!("expected a finite positive rate, got {per_second}"),
+                });
+            }
+        }
+
+        let bucket = limit.requests_per_second.map(|per_second| {
+            let capacity = limit.burst.max(1) as f64;
+
+            Mutex::new(TokenBucket {
+                per_second,
+                capacity,
+                tokens: capacity,
+                refilled_at: Instant::now(),
+            })
+        });
+
+        Ok(Self {
+            bucket,
+            in_flight: limit
+                .max_in_flight
+                .map(|i| Arc::new(Semaphore::new(i.max(1)))),
+            metrics: Mutex::new(WaitMetrics::default()),
+        })
+    }
+
+    /// Waits until a request may be sent. The returned permit must be held
+    /// until the response has been read.
+    pub async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let started = Instant::now();
+        let mut delayed = false;
+
+        // only requests that are about to be sent take a token
+        let permit = match &self.in_flight {
+            Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+                Ok(permit) => Some(permit),
+                Err(_) => {
+                    delayed = true;
+                    semaphore.clone().acquire_owned().await.ok()
+                }
+            },
+            None => None,
+        };
+
+        if let Some(bucket) = &self.bucket {
+            let wait = lock(bucket).reserve();
+            if !wait.is_zero() {
+                delayed = true;
+                sleep(wait).await;
+            }
+        }
+
+        let waited = if delayed {
+            started.elapsed()
+        } else {
+            Duration::ZERO
+        };
+        let mut metrics = lock(&self.metrics);
+        metrics.requests += 1;
+        metrics.delayed += delayed as u64;
+        metrics.total_wait += waited;
+        metrics.max_wait = metrics.max_wait.max(waited);
+
+        permit
+    }
+
+    pub fn metrics(&self) -> WaitMetrics {
+        *lock(&self.metrics)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_average_wait() {
+        let metrics = |requests, total_wait| WaitMetrics {
+            requests,
+            total_wait,
+            ..Default::default()
+        };
+
+        assert_eq!(metrics(0, Duration::ZERO).average_wait(), Duration::ZERO);
+        assert_eq!(
+            metrics(4, Duration::from_secs(1)).average_wait(),
+            Duration::from_millis(250)
+        );
+        assert_eq!(
+            metrics(1 << 32, Duration::from_secs(1 << 32)).average_wait(),
+            Duration::from_secs(1)
+        );
+        assert_eq!(
+            metrics(u64::MAX, Duration::from_secs(u64::MAX)).average_wait(),
+            Duration::from_secs(1)
+        );
+    }
+
+    #[test]
+    fn test_invalid_rate() {
+        let limit = |requests_per_second| RateLimit {
+            requests_per_second,
+            ..Default::default()
+        };
+
+        assert!(RateLimiter::new(limit(None)).is_ok());
+        assert!(RateLimiter::new(limit(Some(0.5))).is_ok());
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                RateLimiter::new(limit(Some(rate))),
+                Err(PwikiError::InvalidConfig {
+                    option: "requests_per_second",
+                    ..
+                })
+            ));
+        }
+    }
+}
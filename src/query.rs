@@ -1,9 +1,10 @@
 use graphql_client::GraphQLQuery;
 
-use crate::error::ApiError;
+use crate::error::ConversionError;
+use crate::interaction::{Interaction, Severity};
 use crate::structure::{
-    DangerousInteraction, DoseMetadata, DoseTimeRange, Duration, RouteOfAdministration, Substance,
-    TimeUnits, UncertainInteraction, UnsafeInteraction,
+    DoseMetadata, DoseTimeRange, Duration, Effect, ROAs, RouteOfAdministration, Substance,
+    SubstanceClass, SubstanceImage, TimeUnits, Tolerance,
 };
 
 #[derive(GraphQLQuery)]
@@ -14,342 +15,373 @@ use crate::structure::{
 )]
 pub struct SubstanceQuery;
 
-macro_rules! vec_impl {
-    ( $($ty:ty),+ $(,)? ) => {
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schema.json",
+    query_path = "src/wiki_api.graphql",
+    response_derives = "Serialize,PartialEq,Debug,Clone"
+)]
+pub struct SubstancesByEffectQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schema.json",
+    query_path = "src/wiki_api.graphql",
+    response_derives = "Serialize,PartialEq,Debug,Clone"
+)]
+pub struct SubstancesByClassQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "schema.json",
+    query_path = "src/wiki_api.graphql",
+    response_derives = "Serialize,PartialEq,Debug,Clone"
+)]
+pub struct EffectsBySubstanceQuery;
+
+/// graphql_client generates its own copy of the `SubstanceFields` fragment
+/// types for every query, so the conversions are stamped out per module.
+macro_rules! substance_fields_impl {
+    ( $($module:ident),+ $(,)? ) => {
         $(
-            impl UnwrapVec<$ty>
-                for Vec<Option<$ty>> {
-                    fn unwrap(self) -> Vec<$ty> {
-                        self.into_iter().filter_map(|u| u).collect()
+            impl TryFrom<$module::SubstanceFields> for Substance {
+                type Error = ConversionError;
+
+                fn try_from(
+                    substance_query: $module::SubstanceFields,
+                ) -> Result<Substance, ConversionError> {
+                    substance_query.into_substance(ConversionMode::Strict)
+                }
+            }
+
+            impl IntoSubstance for $module::SubstanceFields {
+                fn into_substance(
+                    self,
+                    mode: ConversionMode,
+                ) -> Result<Substance, ConversionError> {
+                    let substance_query = self;
+                    let name = substance_query.name.unwrap_or_default();
+                    let mut warnings = Vec::new();
+
+                    let routes_of_administration = substance_query
+                        .roas
+                        .unwrap_or_default()
+                        .into_iter()
+                        .flatten()
+                        .map(|roa| {
+                            let ty = roa.name.map(|i| i.into()).unwrap_or_default();
+                            let mut phases = PhaseContext {
+                                substance: &name,
+                                route: ty,
+                                mode,
+                                warnings: &mut warnings,
+                            };
+                            let duration = match roa.duration {
+                                Some(d) => Duration {
+                                    afterglow: phases.convert(
+                                        "afterglow",
+                                        d.afterglow.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                    comeup: phases.convert(
+                                        "comeup",
+                                        d.comeup.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                    duration: phases.convert(
+                                        "duration",
+                                        d.duration.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                    offset: phases.convert(
+                                        "offset",
+                                        d.offset.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                    onset: phases.convert(
+                                        "onset",
+                                        d.onset.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                    peak: phases.convert(
+                                        "peak",
+                                        d.peak.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                    total: phases.convert(
+                                        "total",
+                                        d.total.map(|i| (i.min, i.max, i.units)),
+                                    )?,
+                                },
+                                None => Duration::default(),
+                            };
+
+                            Ok(RouteOfAdministration {
+                                ty,
+                                dose_metadata: roa.dose.map(|i| i.into()).unwrap_or_default(),
+                                duration,
+                                // a missing bound is unknown, not zero
+                                bioavailability: roa.bioavailability.and_then(|i| {
+                                    match (i.min, i.max) {
+                                        (Some(min), Some(max)) if min > 0.0 && max > 0.0 => {
+                                            Some(min..max)
+                                        }
+                                        _ => None,
+                                    }
+                                }),
+                            })
+                        })
+                        .collect::<Result<Vec<_>, ConversionError>>()?;
+
+                    Ok(Substance {
+                        name,
+                        url: substance_query.url,
+                        featured: substance_query.featured.unwrap_or_default(),
+                        summary: substance_query.summary,
+                        common_names: substance_query
+                            .common_names
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .collect(),
+                        class: substance_query.class.map(|i| i.into()).unwrap_or_default(),
+                        addiction_potential: substance_query.addiction_potential,
+                        toxicity: substance_query
+                            .toxicity
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .collect(),
+                        tolerance: substance_query.tolerance.map(|i| i.into()),
+                        images: substance_query
+                            .images
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .map(|i| i.into())
+                            .collect(),
+                        cross_tolerances: substance_query
+                            .cross_tolerances
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .collect(),
+                        routes_of_administration,
+                        interactions: substance_query
+                            .uncertain_interactions
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .map(Interaction::from)
+                            .chain(
+                                substance_query
+                                    .unsafe_interactions
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .flatten()
+                                    .map(Interaction::from),
+                            )
+                            .chain(
+                                substance_query
+                                    .dangerous_interactions
+                                    .unwrap_or_default()
+                                    .into_iter()
+                                    .flatten()
+                                    .map(Interaction::from),
+                            )
+                            .collect(),
+                        warnings,
+                    })
+                }
+            }
+
+            impl From<$module::SubstanceFieldsClass> for SubstanceClass {
+                fn from(class: $module::SubstanceFieldsClass) -> SubstanceClass {
+                    SubstanceClass {
+                        chemical: class
+                            .chemical
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .collect(),
+                        psychoactive: class
+                            .psychoactive
+                            .unwrap_or_default()
+                            .into_iter()
+                            .flatten()
+                            .collect(),
                     }
                 }
-        )*
-    };
-}
-
-impl SubstanceQuery {
-    async fn substance_data_internal(
-        substance: impl AsRef<str>,
-    ) -> Result<graphql_client::Response<substance_query::ResponseData>, Box<dyn std::error::Error>>
-    {
-        // this is the important line
-        let request_body = SubstanceQuery::build_query(substance_query::Variables {
-            substance: substance.as_ref().into(),
-        });
-
-        let client = reqwest::Client::new();
-        let res = client
-            .post("https://api.psychonautwiki.org/")
-            .json(&request_body)
-            .send()
-            .await?;
-        let response_body: graphql_client::Response<substance_query::ResponseData> =
-            res.json().await?;
-
-        if let Some(e) = response_body.errors {
-            let messages = e.into_iter().map(|e| e.message).collect();
-            return Err(Box::new(ApiError::new(messages)));
-        }
-
-        Ok(response_body)
-    }
+            }
+
+            impl From<$module::SubstanceFieldsTolerance> for Tolerance {
+                fn from(tolerance: $module::SubstanceFieldsTolerance) -> Tolerance {
+                    Tolerance {
+                        full: tolerance.full,
+                        half: tolerance.half,
+                        zero: tolerance.zero,
+                    }
+                }
+            }
 
-    pub async fn substance_data(
-        substance: impl AsRef<str>,
-    ) -> Result<Vec<Substance>, Box<dyn std::error::Error>>
-    {
-        let r = SubstanceQuery::substance_data_internal(substance).await?;
-        let s = r.data.and_then(|o| o.substances).ok_or_else(|| {
-            Box::new(ApiError::new(vec![
-                "Missing substance data.".to_string()
-            ]))
-        })?;
-
-        Ok(s.into_iter().filter_map(|i| i).map(|i| i.into()).collect::<Vec<_>>())
-    }
-}
+            impl From<$module::SubstanceFieldsImages> for SubstanceImage {
+                fn from(image: $module::SubstanceFieldsImages) -> SubstanceImage {
+                    SubstanceImage {
+                        thumb: image.thumb,
+                        image: image.image,
+                    }
+                }
+            }
 
-trait GetSubstances {
-    fn substances(self) -> Vec<substance_query::SubstanceQuerySubstances>;
-}
+            impl From<$module::SubstanceFieldsDangerousInteractions> for Interaction {
+                fn from(
+                    interaction: $module::SubstanceFieldsDangerousInteractions,
+                ) -> Interaction {
+                    Interaction::new(interaction.name.unwrap_or_default(), Severity::Dangerous)
+                }
+            }
 
-impl GetSubstances for substance_query::ResponseData {
-    fn substances(self) -> Vec<substance_query::SubstanceQuerySubstances> {
-        self.substances.unwrap().unwrap()
-    }
-}
+            impl From<$module::SubstanceFieldsUnsafeInteractions> for Interaction {
+                fn from(
+                    interaction: $module::SubstanceFieldsUnsafeInteractions,
+                ) -> Interaction {
+                    Interaction::new(interaction.name.unwrap_or_default(), Severity::Unsafe)
+                }
+            }
 
-trait UnwrapVec<T> {
-    fn unwrap(self) -> Vec<T>;
+            impl From<$module::SubstanceFieldsUncertainInteractions> for Interaction {
+                fn from(
+                    interaction: $module::SubstanceFieldsUncertainInteractions,
+                ) -> Interaction {
+                    Interaction::new(interaction.name.unwrap_or_default(), Severity::Uncertain)
+                }
+            }
+
+            impl From<$module::SubstanceFieldsRoasDose> for DoseMetadata {
+                fn from(dosage: $module::SubstanceFieldsRoasDose) -> DoseMetadata {
+                    DoseMetadata {
+                        units: dosage.units.unwrap_or_default().into(),
+                        threshold: dosage.threshold,
+                        heavy: dosage.heavy,
+                        common: dosage
+                            .common
+                            .map(|i| i.min.unwrap_or_default()..i.max.unwrap_or_default()),
+                        light: dosage
+                            .light
+                            .map(|i| i.min.unwrap_or_default()..i.max.unwrap_or_default()),
+                        strong: dosage
+                            .strong
+                            .map(|i| i.min.unwrap_or_default()..i.max.unwrap_or_default()),
+                    }
+                }
+            }
+        )+
+    };
 }
 
-vec_impl! {
-    substance_query::SubstanceQuerySubstances,
-    substance_query::SubstanceQuerySubstancesRoas,
-    substance_query::SubstanceQuerySubstancesUncertainInteractions,
-    substance_query::SubstanceQuerySubstancesUnsafeInteractions,
-    substance_query::SubstanceQuerySubstancesDangerousInteractions,
+/// How conversions treat duration phases with data that cannot be
+/// interpreted, such as unknown time units or negative lengths.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionMode {
+    /// Reject the whole substance with a [`ConversionError`].
+    Strict,
+    /// Drop the offending phase and record it in [`Substance::warnings`].
+    #[default]
+    Lenient,
 }
 
-impl From<crate::query::substance_query::SubstanceQuerySubstances> for Substance {
-    fn from(substance_query: crate::query::substance_query::SubstanceQuerySubstances) -> Substance {
-        Substance {
-            name: substance_query.name.unwrap_or_default(),
-            cross_tolerances: substance_query
-                .cross_tolerances
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|i| i)
-                .collect(),
-            dangerous_interactions: substance_query
-                .dangerous_interactions
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|i| i)
-                .map(|i| i.into())
-                .collect(),
-            routes_of_administration: substance_query
-                .roas
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|i| i)
-                .map(|i| i.into())
-                .collect(),
-            uncertain_interactions: substance_query
-                .uncertain_interactions
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|i| i)
-                .map(|i| i.into())
-                .collect(),
-            unsafe_interactions: substance_query
-                .unsafe_interactions
-                .unwrap_or_default()
-                .into_iter()
-                .filter_map(|i| i)
-                .map(|i| i.into())
-                .collect(),
-        }
-    }
+/// Conversion of the `SubstanceFields` fragment of any query module.
+pub trait IntoSubstance {
+    fn into_substance(self, mode: ConversionMode) -> Result<Substance, ConversionError>;
 }
 
-impl From<crate::query::substance_query::SubstanceQuerySubstancesDangerousInteractions>
-    for DangerousInteraction
-{
-    fn from(
-        interaction: crate::query::substance_query::SubstanceQuerySubstancesDangerousInteractions,
-    ) -> DangerousInteraction {
-        DangerousInteraction {
-            name: interaction.name.unwrap_or_default(),
-        }
-    }
+/// Where the duration phases being converted came from.
+struct PhaseContext<'a> {
+    substance: &'a str,
+    route: ROAs,
+    mode: ConversionMode,
+    warnings: &'a mut Vec<ConversionError>,
 }
 
-impl From<crate::query::substance_query::SubstanceQuerySubstancesUnsafeInteractions>
-    for UnsafeInteraction
-{
-    fn from(
-        interaction: crate::query::substance_query::SubstanceQuerySubstancesUnsafeInteractions,
-    ) -> UnsafeInteraction {
-        UnsafeInteraction {
-            name: interaction.name.unwrap_or_default(),
+impl PhaseContext<'_> {
+    fn convert(
+        &mut self,
+        phase: &'static str,
+        range: Option<(Option<f64>, Option<f64>, Option<String>)>,
+    ) -> Result<Option<DoseTimeRange>, ConversionError> {
+        let Some((min, max, units)) = range else {
+            return Ok(None);
+        };
+
+        let raw_units = units.unwrap_or_default();
+        let units = TimeUnits::from(raw_units.clone());
+        let start = min.unwrap_or_default();
+        let end = max.unwrap_or_default();
+        let midpoint = (start + end) / 2.0;
+
+        let duration = match units.as_secs_f64() {
+            Some(unit) => std::time::Duration::try_from_secs_f64(end * unit)
+                .map_err(|_| self.error(phase, "max", end.to_string())),
+            None => Err(self.error(phase, "units", raw_units)),
+        };
+
+        match (duration, self.mode) {
+            (Ok(duration), _) => Ok(Some(DoseTimeRange {
+                duration,
+                units,
+                start,
+                midpoint,
+                end,
+            })),
+            (Err(e), ConversionMode::Strict) => Err(e),
+            (Err(e), ConversionMode::Lenient) => {
+                self.warnings.push(e);
+                Ok(None)
+            }
         }
     }
-}
 
-impl From<crate::query::substance_query::SubstanceQuerySubstancesUncertainInteractions>
-    for UncertainInteraction
-{
-    fn from(
-        interaction: crate::query::substance_query::SubstanceQuerySubstancesUncertainInteractions,
-    ) -> UncertainInteraction {
-        UncertainInteraction {
-            name: interaction.name.unwrap_or_default(),
+    fn error(&self, phase: &'static str, field: &'static str, value: String) -> ConversionError {
+        ConversionError {
+            substance: self.substance.to_string(),
+            route: self.route,
+            phase: phase.to_string(),
+            field: field.to_string(),
+            value,
         }
     }
 }
 
-impl From<crate::query::substance_query::SubstanceQuerySubstancesRoas> for RouteOfAdministration {
-    fn from(roa: crate::query::substance_query::SubstanceQuerySubstancesRoas) -> Self {
-        RouteOfAdministration {
-            ty: roa.name.map(|i| i.into()).unwrap_or_default(),
-            dose_metadata: roa.dose.map(|i| i.into()).unwrap_or_default(),
-            duration: roa.duration.map(|i| i.into()).unwrap_or_default(),
-        }
-    }
+substance_fields_impl! {
+    substance_query,
+    substances_by_effect_query,
+    substances_by_class_query,
 }
 
-impl From<crate::query::substance_query::SubstanceQuerySubstancesRoasDose> for DoseMetadata {
-    fn from(dosage: crate::query::substance_query::SubstanceQuerySubstancesRoasDose) -> DoseMetadata {
-        DoseMetadata {
-            units: dosage.units.unwrap_or_default().into(),
-            threshold: dosage.threshold,
-            heavy: dosage.heavy,
-            common: dosage
-                .common
-                .map(|i| i.min.unwrap_or_default()..i.max.unwrap_or_default()),
-            light: dosage
-                .light
-                .map(|i| i.min.unwrap_or_default()..i.max.unwrap_or_default()),
-            strong: dosage
-                .strong
-                .map(|i| i.min.unwrap_or_default()..i.max.unwrap_or_default()),
-        }
-    }
+/// A document looking up `count` substances at once, aliasing the results
+/// of `substances(query: $qN)` as `sN`. The results decode into
+/// `substance_query` types, which share the `SubstanceFields` fragment.
+pub(crate) fn batch_query(count: usize) -> String {
+    // the fragment is the last definition in the document
+    let document = include_str!("wiki_api.graphql");
+    let fragment = &document[document
+        .find("fragment SubstanceFields")
+        .expect("SubstanceFields fragment is defined")..];
+
+    let variables: Vec<_> = (0..count).map(|i| format!("$q{i}: String!")).collect();
+    let fields: String = (0..count)
+        .map(|i| {
+            format!("    s{i}: substances(query: $q{i}) {{\n        ...SubstanceFields\n    }}\n")
+        })
+        .collect();
+
+    format!(
+        "query SubstancesBatchQuery({}) {{\n{fields}}}\n\n{fragment}",
+        variables.join(", ")
+    )
 }
 
-impl From<crate::query::substance_query::SubstanceQuerySubstancesRoasDuration> for Duration {
+impl From<effects_by_substance_query::EffectsBySubstanceQueryEffectsBySubstance> for Effect {
     fn from(
-        duration: crate::query::substance_query::SubstanceQuerySubstancesRoasDuration,
-    ) -> Duration {
-        Duration {
-            afterglow: duration.afterglow.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
-            comeup: duration.comeup.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
-            duration: duration.duration.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
-            offset: duration.offset.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
-            onset: duration.onset.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
-            peak: duration.peak.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
-            total: duration.total.map(|i| {
-                let units = i.units.unwrap_or_default().into();
-                let start = i.min.unwrap_or_default();
-                let end = i.max.unwrap_or_default();
-                let midpoint = (start + end) / 2.0;
-
-                let duration = match units {
-                    TimeUnits::Minutes => std::time::Duration::from_secs_f64(end * 60.0),
-                    TimeUnits::Hours => std::time::Duration::from_secs_f64(end * 3600.0),
-                    TimeUnits::Seconds => std::time::Duration::from_secs_f64(end),
-                    _ => unimplemented!(),
-                };
-
-                DoseTimeRange {
-                    duration,
-                    units,
-                    start,
-                    midpoint,
-                    end,
-                }
-            }),
+        effect: effects_by_substance_query::EffectsBySubstanceQueryEffectsBySubstance,
+    ) -> Effect {
+        Effect {
+            name: effect.name.unwrap_or_default(),
+            url: effect.url,
         }
     }
 }
@@ -358,10 +390,80 @@ impl From<crate::query::substance_query::SubstanceQuerySubstancesRoasDuration> f
 mod test {
     use super::*;
 
-    #[tokio::test]
-    async fn test_query() {
-        dbg!(SubstanceQuery::substance_data("LSD")
-            .await
-            .unwrap());
+    fn lsd_with_onset_units(units: &str) -> substance_query::SubstanceFields {
+        serde_json::from_value(serde_json::json!({
+            "name": "LSD",
+            "roas": [{
+                "name": "oral",
+                "duration": {
+                    "onset": { "min": 1, "max": 2, "units": units },
+                    "total": { "min": 8, "max": 12, "units": "hours" },
+                },
+            }],
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_invalid_time_units() {
+        let err = Substance::try_from(lsd_with_onset_units("fortnights")).unwrap_err();
+        assert_eq!(
+            err,
+            ConversionError {
+                substance: "LSD".to_string(),
+                route: ROAs::Oral,
+                phase: "onset".to_string(),
+                field: "units".to_string(),
+                value: "fortnights".to_string(),
+            }
+        );
+
+        let substance = lsd_with_onset_units("fortnights")
+            .into_substance(ConversionMode::Lenient)
+            .unwrap();
+        let duration = &substance.routes_of_administration[0].duration;
+        assert!(duration.onset.is_none());
+        assert!(duration.total.is_some());
+        assert_eq!(substance.warnings, vec![err]);
+    }
+
+    #[test]
+    fn test_partial_bioavailability() {
+        let substance = |oral: serde_json::Value| {
+            let fields: substance_query::SubstanceFields =
+                serde_json::from_value(serde_json::json!({
+                    "name": "LSD",
+                    "roas": [
+                        { "name": "oral", "bioavailability": oral },
+                        { "name": "sublingual", "bioavailability": { "min": 50, "max": 60 } },
+                    ],
+                }))
+                .unwrap();
+            Substance::try_from(fields).unwrap()
+        };
+
+        for oral in [
+            serde_json::json!({ "min": null, "max": 80 }),
+            serde_json::json!({ "min": 70, "max": null }),
+            serde_json::json!({ "min": null, "max": null }),
+            serde_json::json!({ "min": 0, "max": 80 }),
+        ] {
+            let substance = substance(oral);
+            assert_eq!(substance.routes_of_administration[0].bioavailability, None);
+            assert_eq!(
+                substance.equivalent_dose(100.0, ROAs::Oral, ROAs::Sublingual),
+                None
+            );
+            assert_eq!(
+                substance.equivalent_dose(100.0, ROAs::Sublingual, ROAs::Oral),
+                None
+            );
+        }
+
+        let substance = substance(serde_json::json!({ "min": 70, "max": 80 }));
+        assert_eq!(
+            substance.routes_of_administration[0].bioavailability,
+            Some(70.0..80.0)
+        );
     }
 }
@@ -0,0 +1,144 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::error::{PwikiError, Result};
+
+/// How requests that fail with a [transient](crate::PwikiError::is_transient)
+/// error are retried, see
+/// [`PwikiClientBuilder::retry`](crate::client::PwikiClientBuilder::retry).
+///
+/// The delay before retry `n` is `base_delay * 2^(n - 1)`, capped at
+/// `max_delay` and then reduced by up to `jitter` of itself so that clients
+/// failing together do not retry together. A `Retry-After` header sent with
+/// a 429 or 5xx response is used as the delay instead, unless it is longer
+/// than `max_delay`: the request then fails right away with the
+/// [`Status`](crate::PwikiError::Status) error rather than retrying earlier
+/// than the endpoint asked or waiting for as long as it likes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    /// Attempts per request, including the first one.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// Fraction of each delay, between 0 and 1, that is randomized. Other
+    /// values are rejected when the client is built.
+    pub jitter: f64,
+    /// Total time allowed for a request, across all attempts and delays.
+    pub deadline: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// Sends every request once, the client's default.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(PwikiError::InvalidConfig {
+                option: "jitter",
+                reason: format!("expected a fraction between 0 and 1, got {}", self.jitter),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// The delay after failed attempt number `attempt`, starting at 1.
+    /// `None` if the endpoint asked to wait longer than `max_delay`.
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if let Some(retry_after) = retry_after {
+            return Some(retry_after).filter(|&i| i <= self.max_delay);
+        }
+
+        let exponential = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        Some(exponential.mul_f64(1.0 - self.jitter * random_fraction()))
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.5,
+            deadline: None,
+        }
+    }
+}
+
+/// A number in `[0, 1)`, good enough to spread out retries without pulling
+/// in a random number generator.
+fn random_fraction() -> f64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as u64,
+    );
+
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(500),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        assert_eq!(policy.delay(1, None), Some(Duration::from_millis(100)));
+        assert_eq!(policy.delay(3, None), Some(Duration::from_millis(400)));
+        assert_eq!(policy.delay(4, None), Some(Duration::from_millis(500)));
+        assert_eq!(policy.delay(40, None), Some(Duration::from_millis(500)));
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_millis(300))),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(86400))), None);
+
+        let policy = RetryPolicy {
+            jitter: 0.5,
+            ..policy
+        };
+        for _ in 0..100 {
+            let delay = policy.delay(4, None).unwrap();
+            assert!(delay > Duration::from_millis(250) && delay <= Duration::from_millis(500));
+        }
+    }
+
+    #[test]
+    fn test_invalid_jitter() {
+        let policy = |jitter| RetryPolicy {
+            jitter,
+            ..Default::default()
+        };
+
+        assert!(policy(0.0).validate().is_ok());
+        assert!(policy(1.0).validate().is_ok());
+        for jitter in [-0.1, 1.5, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                policy(jitter).validate(),
+                Err(PwikiError::InvalidConfig {
+                    option: "jitter",
+                    ..
+                })
+            ));
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use crate::database::SubstanceDatabase;
+use crate::structure::{name_key, Substance};
+
+/// How a [`SearchHit`] matched the query, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MatchKind {
+    Exact,
+    /// The query is the start of the matched term.
+    Prefix,
+    /// The query is a few typos away from the matched term.
+    Fuzzy,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// The substance's name.
+    pub name: String,
+    /// The name or common name that matched, e.g. "Molly" for MDMA.
+    pub matched: String,
+    pub kind: MatchKind,
+    /// Edits between the query and `matched`, or the characters left to
+    /// type for a prefix match.
+    pub distance: usize,
+}
+
+/// An offline index over the names and common names of a set of
+/// substances, for autocompletion and typo-tolerant lookups.
+///
+/// Case and punctuation are ignored throughout, so "2cb" finds "2C-B".
+#[derive(Debug, Clone, Default)]
+pub struct SearchIndex {
+    /// Sorted by key, so that completions of a prefix are adjacent.
+    terms: Vec<Term>,
+    names: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    key: Vec<char>,
+    counts: CharCounts,
+    term: String,
+    /// Index into `names`.
+    substance: usize,
+}
+
+impl SearchIndex {
+    pub fn new<'a>(substances: impl IntoIterator<Item = &'a Substance>) -> Self {
+        let mut index = Self::default();
+
+        for substance in substances {
+            let id = index.names.len();
+            index.names.push(substance.name.clone());
+
+            for term in std::iter::once(&substance.name).chain(&substance.common_names) {
+                let key: Vec<char> = name_key(term).chars().collect();
+                index.terms.push(Term {
+                    counts: CharCounts::new(&key),
+                    key,
+                    term: term.clone(),
+                    substance: id,
+                });
+            }
+        }
+
+        index.terms.sort_by(|a, b| a.key.cmp(&b.key));
+        index
+            .terms
+            .dedup_by(|a, b| a.key == b.key && a.substance == b.substance);
+        index
+    }
+
+    /// Substances matching `query` exactly, by prefix or with a few typos,
+    /// best matches first and at most one hit per substance.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query: Vec<char> = name_key(query).chars().collect();
+        let max_distance = match query.len() {
+            0..=2 => 0,
+            3..=5 => 1,
+            _ => 2,
+        };
+
+        self.ranked(&query, max_distance, limit)
+    }
+
+    /// Substances with a name or common name starting with `prefix`,
+    /// shortest completions first.
+    pub fn autocomplete(&self, prefix: &str, limit: usize) -> Vec<SearchHit> {
+        let prefix: Vec<char> = name_key(prefix).chars().collect();
+        if prefix.is_empty() {
+            return Vec::new();
+        }
+
+        let start = self.terms.partition_point(|i| i.key < prefix);
+        let candidates = self.terms[start..]
+            .iter()
+            .take_while(|i| i.key.starts_with(&prefix))
+            .enumerate()
+            .map(|(i, term)| Candidate {
+                term: start + i,
+                kind: if term.key.len() == prefix.len() {
+                    MatchKind::Exact
+                } else {
+                    MatchKind::Prefix
+                },
+                distance: term.key.len() - prefix.len(),
+            });
+
+        self.best_per_substance(candidates, limit)
+    }
+
+    /// Close matches to offer when `query` names no substance exactly,
+    /// allowing more typos than [`SearchIndex::search`]. Empty if `query`
+    /// is an exact match.
+    pub fn did_you_mean(&self, query: &str, limit: usize) -> Vec<SearchHit> {
+        let query: Vec<char> = name_key(query).chars().collect();
+        let hits = self.ranked(&query, (query.len() / 2).clamp(2, 3), limit);
+
+        if hits.first().is_some_and(|i| i.kind == MatchKind::Exact) {
+            return Vec::new();
+        }
+        hits
+    }
+
+    fn ranked(&self, query: &[char], max_distance: usize, limit: usize) -> Vec<SearchHit> {
+        if query.is_empty() {
+            return Vec::new();
+        }
+
+        let counts = CharCounts::new(query);
+        let mut rows = Rows::default();
+        let candidates = self.terms.iter().enumerate().filter_map(|(i, term)| {
+            let (kind, distance) = if term.key == query {
+                (MatchKind::Exact, 0)
+            } else if term.key.starts_with(query) {
+                (MatchKind::Prefix, term.key.len() - query.len())
+            } else if term.key.len().abs_diff(query.len()) <= max_distance
+                && counts.min_distance(&term.counts) <= max_distance
+            {
+                let distance = edit_distance(query, &term.key, max_distance, &mut rows)?;
+                (MatchKind::Fuzzy, distance)
+            } else {
+                return None;
+            };
+
+            Some(Candidate {
+                term: i,
+                kind,
+                distance,
+            })
+        });
+
+        self.best_per_substance(candidates, limit)
+    }
+
+    /// Keeps the best candidate of each substance and turns the top `limit`
+    /// into hits, allocating only for those.
+    fn best_per_substance(
+        &self,
+        candidates: impl Iterator<Item = Candidate>,
+        limit: usize,
+    ) -> Vec<SearchHit> {
+        let mut best: Vec<Option<Candidate>> = vec![None; self.names.len()];
+        for candidate in candidates {
+            let slot = &mut best[self.terms[candidate.term].substance];
+            if slot.is_none_or(|i| candidate.rank() < i.rank()) {
+                *slot = Some(candidate);
+            }
+        }
+
+        let mut best: Vec<_> = best.into_iter().flatten().collect();
+        best.sort_unstable_by(|a, b| {
+            let name = |i: &Candidate| &self.names[self.terms[i.term].substance];
+            (a.rank(), name(a)).cmp(&(b.rank(), name(b)))
+        });
+
+        best.into_iter()
+            .take(limit)
+            .map(|i| {
+                let term = &self.terms[i.term];
+                SearchHit {
+                    name: self.names[term.substance].clone(),
+                    matched: term.term.clone(),
+                    kind: i.kind,
+                    distance: i.distance,
+                }
+            })
+            .collect()
+    }
+}
+
+impl From<&SubstanceDatabase> for SearchIndex {
+    fn from(db: &SubstanceDatabase) -> Self {
+        Self::new(db.substances())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    /// Index into `SearchIndex::terms`.
+    term: usize,
+    kind: MatchKind,
+    distance: usize,
+}
+
+impl Candidate {
+    fn rank(&self) -> (MatchKind, usize) {
+        (self.kind, self.distance)
+    }
+}
+
+/// How often each character occurs in a key, with unrelated characters
+/// sharing buckets.
+#[derive(Debug, Clone)]
+struct CharCounts([u8; 32]);
+
+impl CharCounts {
+    fn new(key: &[char]) -> Self {
+        let mut counts = [0u8; 32];
+        for &i in key {
+            let bucket = &mut counts[i as usize % 32];
+            *bucket = bucket.saturating_add(1);
+        }
+        Self(counts)
+    }
+
+    /// A lower bound of the edit distance between the keys, as each edit
+    /// changes the counts of at most two characters by one.
+    fn min_distance(&self, other: &CharCounts) -> usize {
+        let (mut surplus, mut missing) = (0, 0);
+        for (a, b) in self.0.iter().zip(&other.0) {
+            surplus += a.saturating_sub(*b) as usize;
+            missing += b.saturating_sub(*a) as usize;
+        }
+        surplus.max(missing)
+    }
+}
+
+/// Rows of the edit distance table, reused between terms.
+#[derive(Default)]
+struct Rows {
+    before: Vec<usize>,
+    previous: Vec<usize>,
+    current: Vec<usize>,
+}
+
+/// Edits, counting a swap of adjacent characters as one, needed to turn `a`
+/// into `b`. `None` if more than `max` are needed.
+fn edit_distance(a: &[char], b: &[char], max: usize, rows: &mut Rows) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    // rolling rows of the optimal string alignment table
+    let Rows {
+        before,
+        previous,
+        current,
+    } = rows;
+    before.clear();
+    before.resize(b.len() + 1, 0);
+    previous.clear();
+    previous.extend(0..=b.len());
+    current.clear();
+    current.resize(b.len() + 1, 0);
+
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+
+        if current.iter().min().is_some_and(|&i| i > max) {
+            return None;
+        }
+        std::mem::swap(before, previous);
+        std::mem::swap(previous, current);
+    }
+
+    Some(previous[b.len()]).filter(|&i| i <= max)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn substance(name: &str, common_names: &[&str]) -> Substance {
+        Substance {
+            name: name.to_string(),
+            common_names: common_names.iter().map(|i| i.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_search() {
+        let index = SearchIndex::new(&[
+            substance("MDMA", &["Molly", "Ecstasy", "XTC"]),
+            substance("MDA", &["Sass"]),
+            substance("Ketamine", &["Special K"]),
+            substance("2C-B", &["Nexus", "Bees"]),
+            substance("2C-E", &[]),
+            substance("LSD", &["Acid"]),
+        ]);
+        let names =
+            |hits: Vec<SearchHit>| -> Vec<String> { hits.into_iter().map(|i| i.name).collect() };
+
+        let hits = index.search("molly", 5);
+        assert_eq!(
+            (hits[0].name.as_str(), hits[0].kind),
+            ("MDMA", MatchKind::Exact)
+        );
+        assert_eq!(names(index.search("2cb", 5)), ["2C-B", "2C-E"]);
+        assert_eq!(names(index.search("ketamin", 5)), ["Ketamine"]);
+        assert!(names(index.search("mdam", 5)).contains(&"MDMA".to_string()));
+        assert!(index.search("", 5).is_empty());
+
+        assert_eq!(names(index.autocomplete("2c", 5)), ["2C-B", "2C-E"]);
+        assert_eq!(names(index.autocomplete("spec", 5)), ["Ketamine"]);
+        assert!(index.autocomplete("xyz", 5).is_empty());
+
+        assert!(index.did_you_mean("Acid", 5).is_empty());
+        let hits = index.did_you_mean("ketamone", 5);
+        assert_eq!(hits[0].matched, "Ketamine");
+        assert_eq!((hits[0].kind, hits[0].distance), (MatchKind::Fuzzy, 1));
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        let chars = |s: &str| s.chars().collect::<Vec<_>>();
+        let distance = |a, b| edit_distance(&chars(a), &chars(b), 3, &mut Rows::default());
+
+        assert_eq!(distance("ketamine", "ketamine"), Some(0));
+        assert_eq!(distance("ketamin", "ketamine"), Some(1));
+        assert_eq!(distance("mdam", "mdma"), Some(1));
+        assert_eq!(distance("lsd", "dmt"), Some(3));
+        assert_eq!(distance("lsd", "ketamine"), None);
+    }
+}
@@ -0,0 +1,181 @@
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+use crate::client::{CatalogOptions, PwikiClient};
+use crate::database::SubstanceDatabase;
+use crate::error::Result;
+use crate::query::{substances_by_class_query, ConversionMode};
+
+/// Version of the snapshot layout written by this crate. Bumped whenever
+/// the selected fields change in a way older readers cannot handle.
+pub const SNAPSHOT_SCHEMA_VERSION: u32 = 1;
+
+/// Where and when a snapshot was taken.
+///
+/// Stored under `extensions.snapshot` of the file, so that a snapshot is
+/// still a valid `SubstanceQuery` response:
+///
+/// ```json
+/// {
+///   "data": { "substances": [...] },
+///   "extensions": {
+///     "snapshot": {
+///       "schema_version": 1,
+///       "fetched_at": "2023-01-01T00:00:00+00:00",
+///       "endpoint": "https://api.psychonautwiki.org/"
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotMetadata {
+    pub schema_version: u32,
+    #[serde(with = "rfc3339")]
+    pub fetched_at: DateTime<Utc>,
+    pub endpoint: String,
+}
+
+/// The full catalog as returned by the API, ready to be written to disk.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub metadata: SnapshotMetadata,
+    substances: Vec<substances_by_class_query::SubstanceFields>,
+}
+
+impl Snapshot {
+    /// Pages through every substance matching `options`.
+    pub async fn fetch(client: &PwikiClient, options: CatalogOptions) -> Result<Self> {
+        let fetched_at = Utc::now();
+        let pages: Vec<_> = client.catalog_pages(options).try_collect().await?;
+
+        Ok(Self {
+            metadata: SnapshotMetadata {
+                schema_version: SNAPSHOT_SCHEMA_VERSION,
+                fetched_at,
+                endpoint: client.endpoint().to_string(),
+            },
+            substances: pages.into_iter().flatten().collect(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.substances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.substances.is_empty()
+    }
+
+    pub fn to_writer(&self, writer: impl Write) -> Result<()> {
+        let file = serde_json::json!({
+            "data": { "substances": &self.substances },
+            "extensions": { "snapshot": &self.metadata },
+        });
+
+        Ok(serde_json::to_writer_pretty(writer, &file)?)
+    }
+
+    /// Writes the snapshot next to `path` and then moves it into place, so
+    /// readers never observe a partially written file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        write_atomically(path.as_ref(), |writer| self.to_writer(writer))
+    }
+
+    pub fn into_database(self, mode: ConversionMode) -> Result<SubstanceDatabase> {
+        let mut json = Vec::new();
+        self.to_writer(&mut json)?;
+
+        SubstanceDatabase::from_json(&json, mode)
+    }
+}
+
+/// Writes `path` through a temporary file next to it that is then moved
+/// into place, so readers never observe a partially written file.
+///
+/// Every call writes its own temporary file, so concurrent writers of the
+/// same path cannot interleave; the last rename wins.
+pub(crate) fn write_atomically(
+    path: &Path,
+    write: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(format!(
+        ".{}.{}.tmp",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let result = (|| {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(&tmp)?);
+        write(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        Ok(std::fs::rename(&tmp, path)?)
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+
+    result
+}
+
+pub(crate) mod rfc3339 {
+    use chrono::{DateTime, Utc};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&time.to_rfc3339())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let time = String::deserialize(deserializer)?;
+
+        DateTime::parse_from_rfc3339(&time)
+            .map(|i| i.with_timezone(&Utc))
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::{mock_endpoint, LSD_FIXTURE};
+
+    #[tokio::test]
+    async fn test_snapshot_round_trip() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder()
+            .endpoint(server.uri())
+            .build()
+            .unwrap();
+        let snapshot = Snapshot::fetch(&client, CatalogOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(snapshot.len(), 1);
+
+        let path = std::env::temp_dir().join(format!("pwiki-snapshot-{}.json", std::process::id()));
+        snapshot.save(&path).unwrap();
+        let db = SubstanceDatabase::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(db.metadata(), Some(&snapshot.metadata));
+        assert_eq!(
+            db.metadata().unwrap().endpoint,
+            format!("{}/", server.uri())
+        );
+        assert_eq!(db.get("Acid").unwrap().routes_of_administration.len(), 2);
+    }
+}
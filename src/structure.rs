@@ -1,19 +1,109 @@
+//! Substances, their routes of administration and ingestions of them.
+//!
+//! # Serialization
+//!
+//! With the `serde` feature enabled every type in this module implements
+//! `Serialize` and `Deserialize`. Objects use the Rust field names, enums
+//! are lowercase strings (`"oral"`, `"µg"`, `"hours"`, `"below_threshold"`),
+//! [`DoseRange`]s are `{ "min": .., "max": .. }`, the `duration` of a
+//! [`DoseTimeRange`] is `{ "secs": .., "nanos": .. }` and timestamps are
+//! RFC 3339 strings. A route of administration looks like:
+//!
+//! ```json
+//! {
+//!   "ty": "sublingual",
+//!   "dose_metadata": {
+//!     "units": "µg",
+//!     "threshold": 15.0,
+//!     "heavy": 300.0,
+//!     "common": { "min": 75.0, "max": 150.0 },
+//!     "light": { "min": 25.0, "max": 75.0 },
+//!     "strong": { "min": 150.0, "max": 300.0 }
+//!   },
+//!   "duration": {
+//!     "onset": {
+//!       "duration": { "secs": 1800, "nanos": 0 },
+//!       "start": 15.0,
+//!       "end": 30.0,
+//!       "midpoint": 22.5,
+//!       "units": "minutes"
+//!     },
+//!     "afterglow": null,
+//!     "comeup": null,
+//!     "duration": null,
+//!     "offset": null,
+//!     "peak": null,
+//!     "total": null
+//!   },
+//!   "bioavailability": { "min": 50.0, "max": 60.0 }
+//! }
+//! ```
+
 #![allow(unused_assignments)]
 
 use std::fmt::Display;
+use std::ops::Range;
 
 use chrono::{DateTime, Utc};
 
+use crate::error::ConversionError;
+use crate::interaction::{Interaction, Severity};
+
 pub type DoseRange = std::ops::Range<f64>;
 
-#[derive(Debug, Clone)]
+/// `{ "min": .., "max": .. }` representation of an optional [`DoseRange`].
+#[cfg(feature = "serde")]
+mod serde_dose_range {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::DoseRange;
+
+    #[derive(Serialize, Deserialize)]
+    struct MinMax {
+        min: f64,
+        max: f64,
+    }
+
+    pub fn serialize<S: Serializer>(
+        range: &Option<DoseRange>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        range
+            .as_ref()
+            .map(|r| MinMax {
+                min: r.start,
+                max: r.end,
+            })
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DoseRange>, D::Error> {
+        Ok(Option::<MinMax>::deserialize(deserializer)?.map(|r| r.min..r.max))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Substance {
     pub name: String,
+    pub url: Option<String>,
+    pub featured: bool,
+    pub summary: Option<String>,
+    pub common_names: Vec<String>,
+    pub class: SubstanceClass,
+    pub addiction_potential: Option<String>,
+    pub toxicity: Vec<String>,
+    pub tolerance: Option<Tolerance>,
+    pub images: Vec<SubstanceImage>,
+    /// Data dropped while converting this substance in
+    /// [`ConversionMode::Lenient`](crate::query::ConversionMode::Lenient).
+    pub warnings: Vec<ConversionError>,
     pub cross_tolerances: Vec<String>,
     pub routes_of_administration: Vec<RouteOfAdministration>,
-    pub uncertain_interactions: Vec<UncertainInteraction>,
-    pub unsafe_interactions: Vec<UnsafeInteraction>,
-    pub dangerous_interactions: Vec<DangerousInteraction>,
+    /// In the order the wiki lists them, uncertain to dangerous.
+    pub interactions: Vec<Interaction>,
 }
 
 impl Substance {
@@ -39,7 +129,7 @@ impl Substance {
             .iter()
             .find(|i| i.ty == dosage.route_of_administration);
 
-        route_of_administration.map(|roa| roa.dosage_type(dosage))
+        route_of_administration.and_then(|roa| roa.dosage_type(dosage))
     }
 
     pub fn route_of_administration(&self, roa: ROAs) -> Option<RouteOfAdministration> {
@@ -48,54 +138,136 @@ impl Substance {
             .find(|i| i.ty == roa)
             .map(|i| i.to_owned())
     }
+
+    /// Estimates the amount taken by `to` that delivers as much substance as
+    /// `amount` taken by `from`, in the same units.
+    ///
+    /// The result spans the least to the most favourable combination of the
+    /// two bioavailability ranges. `None` if either route lacks
+    /// bioavailability data or `to` may not absorb anything at all.
+    pub fn equivalent_dose(&self, amount: f64, from: ROAs, to: ROAs) -> Option<DoseRange> {
+        let from = self.route_of_administration(from)?.bioavailability?;
+        let to = self.route_of_administration(to)?.bioavailability?;
+
+        if to.start <= 0.0 {
+            return None;
+        }
+
+        Some(amount * from.start / to.end..amount * from.end / to.start)
+    }
+
+    /// Whether `name` is this substance's name or one of its common names,
+    /// ignoring case and punctuation, e.g. "2cb" for "2C-B".
+    pub fn is_named(&self, name: &str) -> bool {
+        let name = name_key(name);
+
+        name_key(&self.name) == name || self.common_names.iter().any(|i| name_key(i) == name)
+    }
+
+    /// The most severe of this substance's interactions that applies to
+    /// `other`, by name, class or family.
+    pub fn interaction_with(&self, other: &Substance) -> Option<&Interaction> {
+        self.interactions.iter().filter(|i| i.applies_to(other)).max()
+    }
+
+    /// Interactions with `severity` or worse.
+    pub fn interactions_at_least(&self, severity: Severity) -> impl Iterator<Item = &Interaction> {
+        self.interactions.iter().filter(move |i| i.severity >= severity)
+    }
+}
+
+/// `name` lowercased with everything but letters and digits removed.
+pub(crate) fn name_key(name: &str) -> String {
+    name.chars()
+        .filter(|i| i.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// The chemical and psychoactive groupings a substance belongs to, e.g.
+/// `chemical: ["Tryptamines"]` and `psychoactive: ["Psychedelics"]`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubstanceClass {
+    pub chemical: Vec<String>,
+    pub psychoactive: Vec<String>,
+}
+
+impl SubstanceClass {
+    /// Whether either list contains `class`, ignoring case.
+    pub fn contains(&self, class: &str) -> bool {
+        self.chemical
+            .iter()
+            .chain(&self.psychoactive)
+            .any(|i| i.eq_ignore_ascii_case(class))
+    }
+}
+
+/// How long it takes for tolerance to build and wear off, as free text
+/// from the wiki, e.g. `half: Some("5-7 days")`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tolerance {
+    pub full: Option<String>,
+    pub half: Option<String>,
+    pub zero: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SubstanceImage {
+    pub thumb: Option<String>,
+    pub image: Option<String>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RouteOfAdministration {
     pub ty: ROAs,
     pub dose_metadata: DoseMetadata,
     pub duration: Duration,
+    /// Percentage of the dose that reaches circulation, e.g. `70.0..80.0`.
+    #[cfg_attr(feature = "serde", serde(with = "serde_dose_range", default))]
+    pub bioavailability: Option<DoseRange>,
 }
 
 impl RouteOfAdministration {
-    /// time since start is hours
+    /// time since start is hours, phases in invalid units are skipped
     pub fn calc_effect(&self, dosage: Ingestion, mut time_since_start: f64) -> f64 {
-        let dosage_type = self.dosage_type(&dosage);
-
-        if let DosageType::BelowThreshold = dosage_type {
+        if let Some(DosageType::BelowThreshold) = self.dosage_type(&dosage) {
             return 0f64;
         }
 
-        if let Some(onset) = &self.duration.onset {
-            if time_since_start <= onset.as_hours().midpoint() {
+        if let Some(onset) = self.duration.onset.as_ref().and_then(DoseTimeRange::as_hours) {
+            if time_since_start <= onset.midpoint() {
                 return 0f64;
             }
 
-            time_since_start -= onset.as_hours().midpoint();
+            time_since_start -= onset.midpoint();
         }
 
-        if let Some(comeup) = &self.duration.comeup {
-            if time_since_start <= comeup.as_hours().midpoint() {
-                return lerp(0.0, 1.0, time_since_start / comeup.as_hours().midpoint());
+        if let Some(comeup) = self.duration.comeup.as_ref().and_then(DoseTimeRange::as_hours) {
+            if time_since_start <= comeup.midpoint() {
+                return lerp(0.0, 1.0, time_since_start / comeup.midpoint());
             }
 
-            time_since_start -= comeup.as_hours().midpoint();
+            time_since_start -= comeup.midpoint();
         }
 
-        if let Some(peak) = &self.duration.peak {
-            if time_since_start <= peak.as_hours().midpoint() {
+        if let Some(peak) = self.duration.peak.as_ref().and_then(DoseTimeRange::as_hours) {
+            if time_since_start <= peak.midpoint() {
                 return 1f64;
             }
 
-            time_since_start -= peak.as_hours().midpoint();
+            time_since_start -= peak.midpoint();
         }
 
-        if let Some(offset) = &self.duration.offset {
-            if time_since_start <= offset.as_hours().midpoint() {
-                return lerp(1.0, 0.0, time_since_start / offset.as_hours().midpoint());
+        if let Some(offset) = self.duration.offset.as_ref().and_then(DoseTimeRange::as_hours) {
+            if time_since_start <= offset.midpoint() {
+                return lerp(1.0, 0.0, time_since_start / offset.midpoint());
             }
 
-            time_since_start -= offset.as_hours().midpoint();
+            time_since_start -= offset.midpoint();
         }
 
         // todo: show this _somehow_
@@ -113,32 +285,32 @@ impl RouteOfAdministration {
             .duration
             .onset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end;
 
         let comeup_end = self
             .duration
             .comeup
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + onset_end;
         let peak_end = self
             .duration
             .peak
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + comeup_end;
         let offset_end = self
             .duration
             .offset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + peak_end;
 
@@ -150,31 +322,31 @@ impl RouteOfAdministration {
             .duration
             .onset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .midpoint();
         let comeup = self
             .duration
             .comeup
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .midpoint()
             + onset;
         let peak = self
             .duration
             .peak
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .midpoint()
             + comeup;
         let offset = self
             .duration
             .offset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .midpoint()
             + peak;
 
@@ -192,31 +364,31 @@ impl RouteOfAdministration {
             .duration
             .onset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .start;
         let onset_end = self
             .duration
             .onset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end;
 
         let comeup_start = self
             .duration
             .comeup
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .start
             + onset_start;
         let comeup_end = self
             .duration
             .comeup
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + onset_end;
 
@@ -234,31 +406,31 @@ impl RouteOfAdministration {
             .duration
             .onset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .start;
         let onset_end = self
             .duration
             .onset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end;
 
         let comeup_start = self
             .duration
             .comeup
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .start
             + onset_start;
         let comeup_end = self
             .duration
             .comeup
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + onset_end;
 
@@ -266,16 +438,16 @@ impl RouteOfAdministration {
             .duration
             .peak
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .start
             + comeup_start;
         let peak_end = self
             .duration
             .peak
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + comeup_end;
 
@@ -283,16 +455,16 @@ impl RouteOfAdministration {
             .duration
             .offset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .start
             + peak_start;
         let offset_end = self
             .duration
             .offset
             .as_ref()
-            .unwrap_or(&DoseTimeRange::ZERO)
-            .as_seconds()
+            .and_then(DoseTimeRange::as_seconds)
+            .unwrap_or_default()
             .end
             + peak_end;
 
@@ -311,6 +483,7 @@ fn lerp(f1: f64, f2: f64, t: f64) -> f64 {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Ingestion {
     pub units: DoseUnits,
     pub amount: f64,
@@ -347,6 +520,52 @@ impl Ingestion {
         self.substance.dosage_type(self)
     }
 
+    /// When the effects of this ingestion are felt, from the earliest onset
+    /// to the latest end of the offset, or of the afterglow with
+    /// `include_afterglow`.
+    ///
+    /// The total duration is used when it ends later than the phases, e.g.
+    /// because some are missing. `None` without duration data for the route,
+    /// or if the data is negative or too large to represent.
+    pub fn active_window(&self, include_afterglow: bool) -> Option<Range<DateTime<Utc>>> {
+        let duration = self
+            .substance
+            .route_of_administration(self.route_of_administration)?
+            .duration;
+        let secs = |range: &Option<DoseTimeRange>, bound: fn(&DoseTimeRange) -> f64| {
+            range
+                .as_ref()
+                .and_then(|i| Some(bound(i) * i.units.as_secs_f64()?))
+        };
+
+        let phases = [
+            &duration.onset,
+            &duration.comeup,
+            &duration.peak,
+            &duration.offset,
+        ];
+        let mut end = phases
+            .into_iter()
+            .filter_map(|i| secs(i, |r| r.end))
+            .sum::<f64>()
+            .max(secs(&duration.total, |r| r.end).unwrap_or_default());
+        if include_afterglow {
+            end += secs(&duration.afterglow, |r| r.end).unwrap_or_default();
+        }
+        let start = secs(&duration.onset, |r| r.start).unwrap_or_default();
+
+        // also rejects NaN
+        if !(0.0 <= start && start < end) {
+            return None;
+        }
+
+        let at = |secs: f64| {
+            let delta = chrono::Duration::try_milliseconds((secs * 1e3) as i64)?;
+            self.timestamp.checked_add_signed(delta)
+        };
+        Some(at(start)?..at(end)?)
+    }
+
     pub fn set_amount(&mut self, amount: f64) {
         self.amount = amount;
     }
@@ -372,72 +591,69 @@ impl Ingestion {
         self.set_units(units);
     }
 
-    pub fn normalise_as_units(&self, units: DoseUnits) -> Self {
-        dbg!(&self.units, &units);
-        match (&self.units, &units) {
-            (DoseUnits::Mg, DoseUnits::G) | (DoseUnits::Ug, DoseUnits::Mg) => Self {
-                amount: self.amount / 1e3,
-                ..self.clone()
-            },
-            (DoseUnits::G, DoseUnits::Mg) | (DoseUnits::Mg, DoseUnits::Ug) => Self {
-                amount: self.amount * 1e3,
-                ..self.clone()
-            },
-            (DoseUnits::Ug, DoseUnits::G) => Self {
-                amount: self.amount / 1e6,
-                ..self.clone()
-            },
-            (DoseUnits::G, DoseUnits::Ug) => Self {
-                amount: self.amount * 1e6,
-                ..self.clone()
-            },
-            (l, r) if l == r => {
-                self.clone()
-            },
-            _ => unreachable!()
-        }
+    /// A copy of this ingestion in `units`, `None` if the amount cannot be
+    /// converted, e.g. from ml to mg or from [`DoseUnits::Invalid`].
+    pub fn normalise_as_units(&self, units: DoseUnits) -> Option<Self> {
+        let scale = match (&self.units, &units) {
+            (DoseUnits::Mg, DoseUnits::G) | (DoseUnits::Ug, DoseUnits::Mg) => 1e-3,
+            (DoseUnits::G, DoseUnits::Mg) | (DoseUnits::Mg, DoseUnits::Ug) => 1e3,
+            (DoseUnits::Ug, DoseUnits::G) => 1e-6,
+            (DoseUnits::G, DoseUnits::Ug) => 1e6,
+            (l, r) if l == r && *l != DoseUnits::Invalid => 1.0,
+            _ => return None,
+        };
+
+        Some(Self {
+            amount: self.amount * scale,
+            units,
+            ..self.clone()
+        })
     }
 }
 
 impl RouteOfAdministration {
-    pub fn dosage_type(&self, dosage: &Ingestion) -> DosageType {
-        dosage.normalise_as_units(self.dose_metadata.units);
+    /// `None` if the dose is in units that cannot be compared with the
+    /// route's, see [`Ingestion::normalise_as_units`].
+    pub fn dosage_type(&self, dosage: &Ingestion) -> Option<DosageType> {
+        let dosage = dosage.normalise_as_units(self.dose_metadata.units)?;
 
         if let Some(heavy) = self.dose_metadata.heavy {
             if dosage.amount >= heavy {
-                return DosageType::Heavy;
+                return Some(DosageType::Heavy);
             }
         }
 
         if let Some(threshold) = self.dose_metadata.threshold {
             if dosage.amount == threshold {
-                return DosageType::Threshold;
+                return Some(DosageType::Threshold);
             }
         }
 
         if let Some(light) = &self.dose_metadata.light {
             if light.contains(&dosage.amount) {
-                return DosageType::Light;
+                return Some(DosageType::Light);
             }
         }
 
         if let Some(common) = &self.dose_metadata.common {
             if common.contains(&dosage.amount) {
-                return DosageType::Common;
+                return Some(DosageType::Common);
             }
         }
 
         if let Some(strong) = &self.dose_metadata.strong {
             if strong.contains(&dosage.amount) {
-                return DosageType::Strong;
+                return Some(DosageType::Strong);
             }
         }
 
-        DosageType::BelowThreshold
+        Some(DosageType::BelowThreshold)
     }
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum DosageType {
     Threshold,
     Heavy,
@@ -448,30 +664,32 @@ pub enum DosageType {
 }
 
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DoseMetadata {
     pub units: DoseUnits,
     pub threshold: Option<f64>,
     pub heavy: Option<f64>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_dose_range", default))]
     pub common: Option<DoseRange>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_dose_range", default))]
     pub light: Option<DoseRange>,
+    #[cfg_attr(feature = "serde", serde(with = "serde_dose_range", default))]
     pub strong: Option<DoseRange>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum DoseUnits {
     Mg,
     Ml,
+    #[cfg_attr(feature = "serde", serde(rename = "µg", alias = "ug"))]
     Ug,
     G,
+    #[default]
     Invalid,
 }
 
-impl Default for DoseUnits {
-    fn default() -> Self {
-        DoseUnits::Invalid
-    }
-}
-
 impl Display for DoseUnits {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -479,7 +697,7 @@ impl Display for DoseUnits {
             DoseUnits::Ml => f.write_str("ml"),
             DoseUnits::Ug => f.write_str("µg"),
             DoseUnits::G => f.write_str("g"),
-            DoseUnits::Invalid => todo!(),
+            DoseUnits::Invalid => f.write_str("invalid"),
         }
     }
 }
@@ -496,32 +714,48 @@ impl From<String> for DoseUnits {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum TimeUnits {
     Minutes,
     Hours,
     Seconds,
+    Days,
+    Weeks,
+    #[default]
     Invalid,
 }
 
+impl TimeUnits {
+    /// Length of one unit in seconds, `None` for [`TimeUnits::Invalid`].
+    pub fn as_secs_f64(self) -> Option<f64> {
+        match self {
+            TimeUnits::Seconds => Some(1.0),
+            TimeUnits::Minutes => Some(60.0),
+            TimeUnits::Hours => Some(3600.0),
+            TimeUnits::Days => Some(86400.0),
+            TimeUnits::Weeks => Some(604800.0),
+            TimeUnits::Invalid => None,
+        }
+    }
+}
+
 impl From<String> for TimeUnits {
     fn from(s: String) -> Self {
         match &*s.to_lowercase() {
             "hours" => TimeUnits::Hours,
             "minutes" => TimeUnits::Minutes,
             "seconds" => TimeUnits::Seconds,
+            "days" => TimeUnits::Days,
+            "weeks" => TimeUnits::Weeks,
             _ => TimeUnits::Invalid,
         }
     }
 }
 
-impl Default for TimeUnits {
-    fn default() -> Self {
-        TimeUnits::Invalid
-    }
-}
-
 #[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Duration {
     pub afterglow: Option<DoseTimeRange>,
     pub comeup: Option<DoseTimeRange>,
@@ -533,6 +767,7 @@ pub struct Duration {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct DoseTimeRange {
     pub duration: std::time::Duration,
     pub start: f64,
@@ -580,157 +815,92 @@ impl DoseTimeRange {
         (self.start + self.end) / 2.0
     }
 
-    pub fn as_hours(&self) -> DoseTimeRange {
-        match &self.units {
-            TimeUnits::Minutes => DoseTimeRange {
-                duration: self.duration,
-                start: self.start / 60.0,
-                end: self.end / 60.0,
-                midpoint: self.midpoint / 60.0,
-                units: TimeUnits::Hours,
-            },
-            TimeUnits::Seconds => DoseTimeRange {
-                duration: self.duration,
-                start: self.start / 3600.0,
-                end: self.end / 3600.0,
-                midpoint: self.midpoint / 3600.0,
-                units: TimeUnits::Hours,
-            },
-            TimeUnits::Hours => self.to_owned(),
-
-            _ => unimplemented!(),
-        }
+    /// Ratio that converts a value in the current units into `units`, `None`
+    /// if either side is [`TimeUnits::Invalid`].
+    fn scale_to(&self, units: TimeUnits) -> Option<f64> {
+        Some(self.units.as_secs_f64()? / units.as_secs_f64()?)
     }
 
-    pub fn as_minutes(&self) -> DoseTimeRange {
-        match &self.units {
-            TimeUnits::Seconds => DoseTimeRange {
-                duration: self.duration,
-                start: self.start / 60.0,
-                end: self.end / 60.0,
-                midpoint: self.midpoint / 60.0,
-                units: TimeUnits::Minutes,
-            },
-            TimeUnits::Hours => DoseTimeRange {
-                duration: self.duration,
-                start: self.start * 60.0,
-                end: self.end * 60.0,
-                midpoint: self.midpoint * 60.0,
-                units: TimeUnits::Minutes,
-            },
-            TimeUnits::Minutes => self.to_owned(),
-
-            _ => unimplemented!(),
-        }
+    /// `None` for ranges in or to [`TimeUnits::Invalid`], as are the other
+    /// `as_*` conversions.
+    pub fn as_units(&self, units: TimeUnits) -> Option<DoseTimeRange> {
+        let scale = self.scale_to(units)?;
+
+        Some(DoseTimeRange {
+            duration: self.duration,
+            start: self.start * scale,
+            end: self.end * scale,
+            midpoint: self.midpoint * scale,
+            units,
+        })
     }
 
-    pub fn as_seconds(&self) -> DoseTimeRange {
-        match &self.units {
-            TimeUnits::Minutes => DoseTimeRange {
-                duration: self.duration,
-                start: self.start * 60.0,
-                end: self.end * 60.0,
-                midpoint: self.midpoint * 60.0,
-                units: TimeUnits::Seconds,
-            },
-            TimeUnits::Hours => DoseTimeRange {
-                duration: self.duration,
-                start: self.start * 3600.0,
-                end: self.end * 3600.0,
-                midpoint: self.midpoint * 3600.0,
-                units: TimeUnits::Seconds,
-            },
-            TimeUnits::Seconds => self.to_owned(),
-
-            _ => unimplemented!(),
-        }
+    pub fn as_seconds(&self) -> Option<DoseTimeRange> {
+        self.as_units(TimeUnits::Seconds)
     }
 
-    pub fn to_hours(&mut self) {
-        match &self.units {
-            TimeUnits::Minutes => {
-                self.set_start(self.start / 60.0);
-                self.set_end(self.end / 60.0);
-                self.recalc_midpoint();
-                self.set_units(TimeUnits::Hours);
-            }
-            TimeUnits::Seconds => {
-                self.set_start(self.start / 3600.0);
-                self.set_end(self.end / 3600.0);
-                self.recalc_midpoint();
-                self.set_units(TimeUnits::Hours);
-            }
-            _ => (),
-        }
+    pub fn as_minutes(&self) -> Option<DoseTimeRange> {
+        self.as_units(TimeUnits::Minutes)
     }
 
-    pub fn to_minutes(&mut self) {
-        match &self.units {
-            TimeUnits::Hours => {
-                self.set_start(self.start * 60.0);
-                self.set_end(self.end * 60.0);
-                self.recalc_midpoint();
-                self.set_units(TimeUnits::Minutes);
-            }
-            TimeUnits::Seconds => {
-                self.set_start(self.start / 60.0);
-                self.set_end(self.end / 60.0);
-                self.recalc_midpoint();
-                self.set_units(TimeUnits::Minutes);
-            }
+    pub fn as_hours(&self) -> Option<DoseTimeRange> {
+        self.as_units(TimeUnits::Hours)
+    }
 
-            _ => (),
+    pub fn as_days(&self) -> Option<DoseTimeRange> {
+        self.as_units(TimeUnits::Days)
+    }
+
+    pub fn as_weeks(&self) -> Option<DoseTimeRange> {
+        self.as_units(TimeUnits::Weeks)
+    }
+
+    pub fn to_units(&mut self, units: TimeUnits) {
+        if let Some(scale) = self.scale_to(units) {
+            self.set_start(self.start * scale);
+            self.set_end(self.end * scale);
+            self.recalc_midpoint();
+            self.set_units(units);
         }
     }
 
     pub fn to_seconds(&mut self) {
-        match &self.units {
-            TimeUnits::Hours => {
-                self.set_start(self.start * 3600.0);
-                self.set_end(self.end * 3600.0);
-                self.recalc_midpoint();
-                self.set_units(TimeUnits::Seconds);
-            }
-            TimeUnits::Minutes => {
-                self.set_start(self.start * 60.0);
-                self.set_end(self.end * 60.0);
-                self.recalc_midpoint();
-                self.set_units(TimeUnits::Seconds);
-            }
+        self.to_units(TimeUnits::Seconds)
+    }
 
-            _ => (),
-        }
+    pub fn to_minutes(&mut self) {
+        self.to_units(TimeUnits::Minutes)
     }
 
-    /// normalise lhs units to rhs
-    pub fn normalise_to_units(&mut self, units: TimeUnits) {
-        match (&self.units, &units) {
-            (.., TimeUnits::Hours) => self.to_hours(),
-            (.., TimeUnits::Minutes) => self.to_minutes(),
-            (.., TimeUnits::Seconds) => self.to_seconds(),
+    pub fn to_hours(&mut self) {
+        self.to_units(TimeUnits::Hours)
+    }
 
-            _ => (),
-        }
+    pub fn to_days(&mut self) {
+        self.to_units(TimeUnits::Days)
     }
-}
 
-#[derive(Debug, Clone)]
-pub struct UncertainInteraction {
-    pub name: String,
-}
+    pub fn to_weeks(&mut self) {
+        self.to_units(TimeUnits::Weeks)
+    }
 
-#[derive(Debug, Clone)]
-pub struct UnsafeInteraction {
-    pub name: String,
+    /// normalise lhs units to rhs
+    pub fn normalise_to_units(&mut self, units: TimeUnits) {
+        self.to_units(units)
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct DangerousInteraction {
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Effect {
     pub name: String,
+    pub url: Option<String>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 /// RoutesOfAdministration
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum ROAs {
     Oral,
     Sublingual,
@@ -744,6 +914,7 @@ pub enum ROAs {
     Subcutaneous,
     Rectal,
     Transdermal,
+    #[default]
     Invalid,
 }
 
@@ -767,23 +938,91 @@ impl From<String> for ROAs {
     }
 }
 
-impl Default for ROAs {
-    fn default() -> Self {
-        Self::Invalid
-    }
-}
-
 #[cfg(test)]
 mod test {
-    use crate::query::SubstanceQuery;
+    use crate::client::PwikiClient;
+    use crate::test_util::{mock_endpoint, LSD_FIXTURE};
 
     use super::*;
 
     #[tokio::test]
     async fn test_dosage_type() {
-        let data = SubstanceQuery::substance_data("LSD").await.unwrap();
-        let ingestion = data[0].new_ingestion(100.0, DoseUnits::Ug, Utc::now(), ROAs::Sublingual);
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder().endpoint(server.uri()).build().unwrap();
+
+        let lsd = client.resolve("lsd").await.unwrap();
+        let ingestion = lsd.new_ingestion(100.0, DoseUnits::Ug, Utc::now(), ROAs::Sublingual);
         let dosage_type = ingestion.dosage_type();
         assert_eq!(dosage_type.unwrap(), DosageType::Common);
+
+        let dosage_type = |amount, units: &str| {
+            let units = DoseUnits::from(units.to_string());
+            lsd.new_ingestion(amount, units, Utc::now(), ROAs::Sublingual).dosage_type()
+        };
+        assert_eq!(dosage_type(0.1, "mg"), Some(DosageType::Common));
+        assert_eq!(dosage_type(1.0, "tabs"), None);
+        assert_eq!(dosage_type(1.0, "ml"), None);
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_serde_round_trip() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder().endpoint(server.uri()).build().unwrap();
+
+        let data = client.substance_data("LSD").await.unwrap();
+        let ingestion = data[0].new_ingestion(100.0, DoseUnits::Ug, Utc::now(), ROAs::Sublingual);
+
+        let json = serde_json::to_value(&ingestion).unwrap();
+        let roa = &json["substance"]["routes_of_administration"][1];
+        assert_eq!(roa["ty"], "sublingual");
+        assert_eq!(roa["dose_metadata"]["units"], "µg");
+        assert_eq!(
+            roa["dose_metadata"]["common"],
+            serde_json::json!({ "min": 75.0, "max": 150.0 })
+        );
+
+        let round_trip: Ingestion = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&round_trip).unwrap(), json);
+        assert_eq!(round_trip.dosage_type(), Some(DosageType::Common));
+    }
+
+    #[test]
+    fn test_time_unit_conversions() {
+        let mut afterglow = DoseTimeRange {
+            duration: std::time::Duration::from_secs(2 * 86400),
+            start: 1.0,
+            end: 2.0,
+            midpoint: 1.5,
+            units: TimeUnits::from("days".to_string()),
+        };
+
+        assert_eq!(afterglow.as_hours().unwrap().end, 48.0);
+        assert_eq!(afterglow.as_weeks().unwrap().end, 2.0 / 7.0);
+        let tabs = DoseTimeRange {
+            units: TimeUnits::from("tabs".to_string()),
+            ..afterglow.clone()
+        };
+        assert!(tabs.as_hours().is_none());
+
+        afterglow.normalise_to_units(TimeUnits::Minutes);
+        assert_eq!(afterglow.units, TimeUnits::Minutes);
+        assert_eq!(afterglow.start, 1440.0);
+        assert_eq!(afterglow.midpoint, 2160.0);
+    }
+
+    #[tokio::test]
+    async fn test_equivalent_dose() {
+        let server = mock_endpoint(LSD_FIXTURE).await;
+        let client = PwikiClient::builder().endpoint(server.uri()).build().unwrap();
+
+        let data = client.substance_data("LSD").await.unwrap();
+        let range = data[0]
+            .equivalent_dose(120.0, ROAs::Oral, ROAs::Sublingual)
+            .unwrap();
+        assert_eq!(range, 120.0..192.0);
+        assert!(data[0]
+            .equivalent_dose(120.0, ROAs::Oral, ROAs::Rectal)
+            .is_none());
     }
 }
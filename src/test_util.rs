@@ -0,0 +1,26 @@
+//! Helpers for running queries against a local stand-in for the wiki API.
+
+use wiremock::matchers::method;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const LSD_FIXTURE: &str = include_str!("../tests/fixtures/lsd.json");
+
+/// Starts a server that answers every POST with `body`.
+pub async fn mock_endpoint(body: &str) -> MockServer {
+    mount(MockServer::start().await, body).await
+}
+
+/// Like [`mock_endpoint`], but the server stops listening when dropped
+/// instead of being returned to wiremock's pool.
+pub async fn exclusive_mock_endpoint(body: &str) -> MockServer {
+    mount(MockServer::builder().start().await, body).await
+}
+
+async fn mount(server: MockServer, body: &str) -> MockServer {
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(body, "application/json"))
+        .mount(&server)
+        .await;
+
+    server
+}